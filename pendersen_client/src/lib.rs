@@ -1,42 +1,236 @@
 use tonic::transport::Channel;
 use tonic::{Request, Response};
-use num_bigint::BigInt;
-use serde::Deserialize;
-use std::fs;
+use pendersen_core::server_key::{self, LoginRecord};
+use pendersen_core::{key_exchange, protocol, DecodeError, Group, KdfParameters, KeyShare, ServerProof, SessionKey};
+use std::collections::HashMap;
 use std::io::{self, Write};
-use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Include the generated gRPC module
 pub mod pb {
     tonic::include_proto!("zkp_auth");
 }
 
+pub mod tls;
+pub use tls::{ChannelBinding, TlsOptions};
+
 use pb::{
     auth_client::AuthClient, RegisterRequest, AuthenticationChallengeRequest,
     AuthenticationAnswerRequest, RegisterResponse, AuthenticationChallengeResponse,
-    AuthenticationAnswerResponse,
+    AuthenticationAnswerResponse, LoginRequest, LoginResponse, KdfParametersRequest,
+    KdfParametersResponse, ValidateSessionRequest, ValidateSessionResponse,
+    ChangeCredentialsRequest, ChangeCredentialsResponse,
 };
 
-// Struct for loading parameters from a JSON file
-#[derive(Deserialize)]
-pub struct Parameters {
-    pub p: String,
-    pub q: String,
-    pub g: String,
-    pub h: String,
+impl From<pb::KdfParameters> for KdfParameters {
+    fn from(kdf: pb::KdfParameters) -> Self {
+        KdfParameters {
+            salt: kdf.salt,
+            memory_kib: kdf.memory_kib,
+            iterations: kdf.iterations,
+            parallelism: kdf.parallelism,
+        }
+    }
 }
 
-// Function to load parameters from a JSON file
-pub fn load_parameters() -> (BigInt, BigInt, BigInt, BigInt) {
-    let file_content = fs::read_to_string("../parameters.json").expect("Unable to read file");
-    let params: Parameters = serde_json::from_str(&file_content).expect("Error parsing JSON");
+impl From<KdfParameters> for pb::KdfParameters {
+    fn from(kdf: KdfParameters) -> Self {
+        pb::KdfParameters {
+            salt: kdf.salt,
+            memory_kib: kdf.memory_kib,
+            iterations: kdf.iterations,
+            parallelism: kdf.parallelism,
+        }
+    }
+}
+
+// Response metadata key in which the server says how many seconds to wait
+pub const RETRY_AFTER_METADATA: &str = "retry-after";
+
+// Why the server refused a request, split out of tonic::Status by code
+#[derive(Debug)]
+pub enum AuthError {
+    // The proof did not verify, e.g. because of a wrong password
+    Unauthenticated(String),
+    // A field was malformed or out of range; the message names it
+    InvalidArgument(String),
+    NotFound(String),
+    FailedPrecondition(String),
+    // Too many failed attempts; retry_after is how long the server asked us to wait
+    ResourceExhausted { message: String, retry_after: Option<Duration> },
+    // We asked for a key exchange and the server's answer to it is missing or unusable
+    KeyExchange(String),
+    // We pinned the server's key and it did not prove it holds it
+    ServerAuthentication(String),
+    Other(Box<tonic::Status>),
+}
+
+impl From<tonic::Status> for AuthError {
+    fn from(status: tonic::Status) -> Self {
+        let message = status.message().to_string();
+        match status.code() {
+            tonic::Code::Unauthenticated => AuthError::Unauthenticated(message),
+            tonic::Code::InvalidArgument => AuthError::InvalidArgument(message),
+            tonic::Code::NotFound => AuthError::NotFound(message),
+            tonic::Code::FailedPrecondition => AuthError::FailedPrecondition(message),
+            tonic::Code::ResourceExhausted => AuthError::ResourceExhausted {
+                retry_after: status
+                    .metadata()
+                    .get(RETRY_AFTER_METADATA)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs),
+                message,
+            },
+            _ => AuthError::Other(Box::new(status)),
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Unauthenticated(message) => write!(f, "authentication failed: {}", message),
+            AuthError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            AuthError::NotFound(message) => write!(f, "not found: {}", message),
+            AuthError::FailedPrecondition(message) => write!(f, "failed precondition: {}", message),
+            AuthError::ResourceExhausted { message, .. } => write!(f, "try again later: {}", message),
+            AuthError::KeyExchange(message) => write!(f, "key exchange failed: {}", message),
+            AuthError::ServerAuthentication(message) => write!(f, "server authentication failed: {}", message),
+            AuthError::Other(status) => write!(f, "{}", status),
+        }
+    }
+}
 
-    let p = BigInt::from_str(&params.p).expect("Invalid P value");
-    let q = BigInt::from_str(&params.q).expect("Invalid Q value");
-    let g = BigInt::from_str(&params.g).expect("Invalid G value");
-    let h = BigInt::from_str(&params.h).expect("Invalid H value");
+impl std::error::Error for AuthError {}
+
+// Session token issued by the server after a successful proof, and the key
+// shared with the server when the login carried a key exchange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub session_id: String,
+    pub expires_at: SystemTime,
+    pub key: Option<SessionKey>,
+}
+
+impl Session {
+    fn new(session_id: String, expires_at: u64, key: Option<SessionKey>) -> Self {
+        Session {
+            session_id,
+            expires_at: UNIX_EPOCH + Duration::from_secs(expires_at),
+            key,
+        }
+    }
+}
+
+// The parts of the server's answer to a login that we check
+struct LoginAnswer {
+    session_id: String,
+    expires_at: u64,
+    server_share: String,
+    server_proof: Option<pb::ServerProof>,
+}
+
+impl From<AuthenticationAnswerResponse> for LoginAnswer {
+    fn from(response: AuthenticationAnswerResponse) -> Self {
+        LoginAnswer {
+            session_id: response.session_id,
+            expires_at: response.expires_at,
+            server_share: response.server_share,
+            server_proof: response.server_proof,
+        }
+    }
+}
+
+impl From<LoginResponse> for LoginAnswer {
+    fn from(response: LoginResponse) -> Self {
+        LoginAnswer {
+            session_id: response.session_id,
+            expires_at: response.expires_at,
+            server_share: response.server_share,
+            server_proof: response.server_proof,
+        }
+    }
+}
 
-    (p, q, g, h)
+// A login we sent, kept until the server answers: our key share if we sent one,
+// and the transcript the session key and the server's proof are computed over
+struct PendingLogin<G: Group> {
+    share: Option<KeyShare<G>>,
+    user: String,
+    r1: G::Element,
+    r2: G::Element,
+    c: G::Scalar,
+}
+
+impl<G: Group> PendingLogin<G> {
+    fn client_share(&self, group: &G) -> String {
+        self.share
+            .as_ref()
+            .map(|share| group.encode_element(share.public()))
+            .unwrap_or_default()
+    }
+
+    // The server's share and the session key, when we sent a share of our own;
+    // the server must answer it once it has accepted ours
+    fn agree(&self, group: &G, server_share: &str) -> Result<Option<(G::Element, SessionKey)>, AuthError> {
+        let share = match &self.share {
+            Some(share) => share,
+            None => return Ok(None),
+        };
+        if server_share.is_empty() {
+            return Err(AuthError::KeyExchange("server sent no key share".to_string()));
+        }
+        let server_share = group
+            .decode_element(server_share)
+            .map_err(|e| AuthError::KeyExchange(format!("invalid server_share: {}", e)))?;
+        let shared = share
+            .agree(group, &server_share)
+            .ok_or_else(|| AuthError::KeyExchange("server share gives no shared secret".to_string()))?;
+        let key = key_exchange::session_key(
+            group,
+            &self.user,
+            (&self.r1, &self.r2),
+            &self.c,
+            (share.public(), &server_share),
+            &shared,
+        );
+        Ok(Some((server_share, key)))
+    }
+
+    // Check the server proved knowledge of the pinned key over this login, our
+    // answer s, the key exchange and the session it issued
+    fn check_server_proof(
+        &self,
+        group: &G,
+        server_key: &G::Element,
+        s: &G::Scalar,
+        server_share: Option<&G::Element>,
+        answer: &LoginAnswer,
+    ) -> Result<(), AuthError> {
+        let proof = answer
+            .server_proof
+            .as_ref()
+            .ok_or_else(|| AuthError::ServerAuthentication("server sent no proof of its identity".to_string()))?;
+        let invalid = |e: DecodeError| AuthError::ServerAuthentication(format!("invalid server_proof: {}", e));
+        let proof = ServerProof {
+            r: group.decode_element(&proof.r).map_err(invalid)?,
+            s: group.decode_scalar(&proof.s).map_err(invalid)?,
+        };
+        let login = LoginRecord {
+            user: &self.user,
+            commitment: (&self.r1, &self.r2),
+            challenge: &self.c,
+            response: s,
+            key_shares: self.share.as_ref().map(KeyShare::public).zip(server_share),
+            session_id: &answer.session_id,
+            expires_at: answer.expires_at,
+        };
+        if !server_key::verify(group, server_key, &login, &proof) {
+            return Err(AuthError::ServerAuthentication("proof does not verify against the pinned server key".to_string()));
+        }
+        Ok(())
+    }
 }
 
 // Function to read user input from the console
@@ -48,11 +242,6 @@ pub fn read_input(prompt: &str) -> String {
     input.trim().to_string()
 }
 
-// Function to perform modular exponentiation
-pub fn pow(base: &BigInt, exp: &BigInt, modulus: &BigInt) -> BigInt {
-    base.modpow(exp, modulus)
-}
-
 // Trait for AuthClient to allow mocking in tests
 pub trait AuthClientTrait {
     fn register(
@@ -60,6 +249,11 @@ pub trait AuthClientTrait {
         request: Request<RegisterRequest>,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<RegisterResponse>, tonic::Status>> + Send + '_>>;
 
+    fn get_kdf_parameters(
+        &mut self,
+        request: Request<KdfParametersRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<KdfParametersResponse>, tonic::Status>> + Send + '_>>;
+
     fn create_authentication_challenge(
         &mut self,
         request: Request<AuthenticationChallengeRequest>,
@@ -69,6 +263,21 @@ pub trait AuthClientTrait {
         &mut self,
         request: Request<AuthenticationAnswerRequest>,
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<AuthenticationAnswerResponse>, tonic::Status>> + Send + '_>>;
+
+    fn login(
+        &mut self,
+        request: Request<LoginRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<LoginResponse>, tonic::Status>> + Send + '_>>;
+
+    fn validate_session(
+        &mut self,
+        request: Request<ValidateSessionRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ValidateSessionResponse>, tonic::Status>> + Send + '_>>;
+
+    fn change_credentials(
+        &mut self,
+        request: Request<ChangeCredentialsRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ChangeCredentialsResponse>, tonic::Status>> + Send + '_>>;
 }
 
 // Implement the trait for the actual AuthClient
@@ -80,6 +289,13 @@ impl AuthClientTrait for AuthClient<Channel> {
         Box::pin(self.register(request))
     }
 
+    fn get_kdf_parameters(
+        &mut self,
+        request: Request<KdfParametersRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<KdfParametersResponse>, tonic::Status>> + Send + '_>> {
+        Box::pin(self.get_kdf_parameters(request))
+    }
+
     fn create_authentication_challenge(
         &mut self,
         request: Request<AuthenticationChallengeRequest>,
@@ -93,52 +309,210 @@ impl AuthClientTrait for AuthClient<Channel> {
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<AuthenticationAnswerResponse>, tonic::Status>> + Send + '_>> {
         Box::pin(self.verify_authentication(request))
     }
+
+    fn login(
+        &mut self,
+        request: Request<LoginRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<LoginResponse>, tonic::Status>> + Send + '_>> {
+        Box::pin(self.login(request))
+    }
+
+    fn validate_session(
+        &mut self,
+        request: Request<ValidateSessionRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ValidateSessionResponse>, tonic::Status>> + Send + '_>> {
+        Box::pin(self.validate_session(request))
+    }
+
+    fn change_credentials(
+        &mut self,
+        request: Request<ChangeCredentialsRequest>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ChangeCredentialsResponse>, tonic::Status>> + Send + '_>> {
+        Box::pin(self.change_credentials(request))
+    }
 }
 
-// Struct for the AuthServiceClient
-pub struct AuthServiceClient<T> {
+// Struct for the AuthServiceClient, generic over the transport and the group the protocol runs in
+pub struct AuthServiceClient<T, G: Group> {
     client: T,
+    group: G,
+    channel_binding: ChannelBinding,
+    key_exchange: bool,
+    // The server's long-term public key, when we insist that it proves it
+    server_key: Option<G::Element>,
+    // Interactive challenges we need to check the answer to, by auth_id
+    pending: HashMap<String, PendingLogin<G>>,
 }
 
-impl AuthServiceClient<AuthClient<Channel>> {
-    pub async fn connect(dst: String) -> Result<Self, Box<dyn std::error::Error>> {
+impl<G: Group> AuthServiceClient<AuthClient<Channel>, G> {
+    pub async fn connect(dst: String, group: G) -> Result<Self, Box<dyn std::error::Error>> {
         let client = AuthClient::connect(dst).await?;
-        Ok(Self { client })
+        Ok(Self::new(client, group))
+    }
+
+    // Connect to an https:// address, verifying the server as `tls` says, and bind
+    // every proof to the TLS session it is sent on
+    pub async fn connect_with_tls(dst: String, group: G, tls: &TlsOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let (channel, binding) = tls.connect(dst).await?;
+        Ok(Self::new(AuthClient::new(channel), group).with_channel_binding(binding))
     }
 }
 
-impl<T: AuthClientTrait + Send + Sync> AuthServiceClient<T> {
+impl<T: AuthClientTrait + Send + Sync, G: Group> AuthServiceClient<T, G> {
+    pub fn new(client: T, group: G) -> Self {
+        Self {
+            client,
+            group,
+            channel_binding: ChannelBinding::default(),
+            key_exchange: false,
+            server_key: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    // Run an ephemeral Diffie-Hellman with every login, so that each Session
+    // carries a key shared with the server
+    pub fn with_key_exchange(mut self, key_exchange: bool) -> Self {
+        self.key_exchange = key_exchange;
+        self
+    }
+
+    // Pin the server's long-term public key Z: every login must come back with a
+    // proof of knowledge of its secret, or no Session is returned
+    pub fn with_server_key(mut self, server_key: G::Element) -> Self {
+        self.server_key = Some(server_key);
+        self
+    }
+
+    // Exporter value of the TLS connection `client` talks over
+    pub fn with_channel_binding(mut self, channel_binding: ChannelBinding) -> Self {
+        self.channel_binding = channel_binding;
+        self
+    }
+
+    pub fn group(&self) -> &G {
+        &self.group
+    }
+
+    // The challenge the server will check our answer against: bound to our key
+    // share when we send one, then to the connection the answer travels on
+    fn answered_challenge(&self, c: G::Scalar, share: Option<&KeyShare<G>>) -> G::Scalar {
+        let c = match share {
+            Some(share) => key_exchange::bind_key_share(&self.group, &c, share.public()),
+            None => c,
+        };
+        match self.channel_binding.current() {
+            Some(binding) => protocol::bind_challenge(&self.group, &c, &binding),
+            None => c,
+        }
+    }
+
+    // Nothing in the exchange itself authenticates server_share, so only run it
+    // when TLS binds the login to the connection or a pinned key proves the share
+    fn key_share(&self) -> Result<Option<KeyShare<G>>, AuthError> {
+        if !self.key_exchange {
+            return Ok(None);
+        }
+        if self.channel_binding.current().is_none() && self.server_key.is_none() {
+            return Err(AuthError::KeyExchange("needs TLS or a pinned server key to authenticate the server".to_string()));
+        }
+        Ok(Some(KeyShare::generate(&self.group)))
+    }
+
+    // Check the server's answer against the login we sent: derive the session
+    // key if we sent a share, and check its proof if we pinned its key
+    fn finish_login(&self, pending: Option<PendingLogin<G>>, s: &G::Scalar, answer: LoginAnswer) -> Result<Session, AuthError> {
+        let exchange = match &pending {
+            Some(pending) => pending.agree(&self.group, &answer.server_share)?,
+            None => None,
+        };
+        if let Some(server_key) = &self.server_key {
+            let pending = pending.as_ref().ok_or_else(|| {
+                AuthError::ServerAuthentication("no record of the challenge to check the proof against".to_string())
+            })?;
+            let server_share = exchange.as_ref().map(|(share, _)| share);
+            pending.check_server_proof(&self.group, server_key, s, server_share, &answer)?;
+        }
+        Ok(Session::new(answer.session_id, answer.expires_at, exchange.map(|(_, key)| key)))
+    }
+
+    // Register y1 = g^x and y2 = h^x together with a proof that we know x
     pub async fn register(
         &mut self,
         user_id: &str,
-        y1: &BigInt,
-        y2: &BigInt,
+        x: &G::Scalar,
+        kdf: &KdfParameters,
     ) -> Result<(), tonic::Status> {
+        let (y1, y2) = protocol::public_key(&self.group, x);
+        let k = self.group.random_scalar();
+        let (r1, r2) = protocol::commitment(&self.group, &k);
+        let c = protocol::registration_challenge(&self.group, user_id, (&y1, &y2), (&r1, &r2), Some(kdf));
+        let s = protocol::response(&self.group, &k, &c, x);
+
         let register_req = RegisterRequest {
             user: user_id.to_string(),
-            y1: y1.to_string(),
-            y2: y2.to_string(),
+            y1: self.group.encode_element(&y1),
+            y2: self.group.encode_element(&y2),
+            kdf: Some(kdf.clone().into()),
+            r1: self.group.encode_element(&r1),
+            r2: self.group.encode_element(&r2),
+            s: self.group.encode_scalar(&s),
         };
 
         self.client.register(Request::new(register_req)).await?;
         Ok(())
     }
 
+    // Salt and cost parameters the user registered with, needed to derive x before logging in
+    pub async fn get_kdf_parameters(&mut self, user_id: &str) -> Result<KdfParameters, tonic::Status> {
+        let kdf_req = KdfParametersRequest {
+            user: user_id.to_string(),
+        };
+
+        let response = self.client.get_kdf_parameters(Request::new(kdf_req)).await?;
+        let kdf: KdfParameters = response
+            .into_inner()
+            .kdf
+            .ok_or_else(|| tonic::Status::failed_precondition("User has no key derivation parameters"))?
+            .into();
+        kdf.validate().map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+
+        Ok(kdf)
+    }
+
+    // Returns the auth_id and the challenge to answer, already bound to the
+    // connection and any key share so that s = k - c * x can be sent as is
     pub async fn create_authentication_challenge(
         &mut self,
         user_id: &str,
-        r1: &BigInt,
-        r2: &BigInt,
-    ) -> Result<(String, BigInt), tonic::Status> {
+        r1: &G::Element,
+        r2: &G::Element,
+    ) -> Result<(String, G::Scalar), tonic::Status> {
+        let share = self.key_share().map_err(|e| tonic::Status::failed_precondition(e.to_string()))?;
         let auth_req = AuthenticationChallengeRequest {
             user: user_id.to_string(),
-            r1: r1.to_string(),
-            r2: r2.to_string(),
+            r1: self.group.encode_element(r1),
+            r2: self.group.encode_element(r2),
         };
 
         let response = self.client.create_authentication_challenge(Request::new(auth_req)).await?;
         let auth_res = response.into_inner();
-        let c = BigInt::from_str(&auth_res.c).expect("Invalid C value");
+        let c = self
+            .group
+            .decode_scalar(&auth_res.c)
+            .map_err(|e| tonic::Status::invalid_argument(format!("invalid c: {}", e)))?;
+
+        let c = self.answered_challenge(c, share.as_ref());
+        if share.is_some() || self.server_key.is_some() {
+            let pending = PendingLogin {
+                share,
+                user: user_id.to_string(),
+                r1: r1.clone(),
+                r2: r2.clone(),
+                c: c.clone(),
+            };
+            self.pending.insert(auth_res.auth_id.clone(), pending);
+        }
 
         Ok((auth_res.auth_id, c))
     }
@@ -146,21 +520,117 @@ impl<T: AuthClientTrait + Send + Sync> AuthServiceClient<T> {
     pub async fn verify_authentication(
         &mut self,
         auth_id: &str,
-        s: &BigInt,
-    ) -> Result<(), tonic::Status> {
+        s: &G::Scalar,
+    ) -> Result<Session, AuthError> {
+        // The server uses up the challenge whatever the outcome, so drop our record too
+        let pending = self.pending.remove(auth_id);
         let auth_ans_req = AuthenticationAnswerRequest {
             auth_id: auth_id.to_string(),
-            s: s.to_string(),
+            s: self.group.encode_scalar(s),
+            client_share: pending.as_ref().map(|pending| pending.client_share(&self.group)).unwrap_or_default(),
         };
 
         let response = self.client.verify_authentication(Request::new(auth_ans_req)).await?;
-        let auth_ans_res = response.into_inner();
+        self.finish_login(pending, s, response.into_inner().into())
+    }
 
-        if auth_ans_res.session_id.is_empty() {
-            println!("Authentication failed");
-        } else {
-            println!("Authentication succeeded with session ID: {}", auth_ans_res.session_id);
-        }
+    // Non-interactive login: derive c = H(parameters, user, y1, y2, r1, r2, timestamp)
+    // locally and send the whole proof in a single request
+    pub async fn login(
+        &mut self,
+        user_id: &str,
+        x: &G::Scalar,
+    ) -> Result<Session, AuthError> {
+        let (y1, y2) = protocol::public_key(&self.group, x);
+        let k = self.group.random_scalar();
+        let (r1, r2) = protocol::commitment(&self.group, &k);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        let c = protocol::login_challenge(&self.group, user_id, (&y1, &y2), (&r1, &r2), &timestamp.to_be_bytes());
+        let share = self.key_share()?;
+        let c = self.answered_challenge(c, share.as_ref());
+        let s = protocol::response(&self.group, &k, &c, x);
+        let pending = PendingLogin {
+            share,
+            user: user_id.to_string(),
+            r1: r1.clone(),
+            r2: r2.clone(),
+            c,
+        };
+
+        let login_req = LoginRequest {
+            user: user_id.to_string(),
+            r1: self.group.encode_element(&r1),
+            r2: self.group.encode_element(&r2),
+            s: self.group.encode_scalar(&s),
+            timestamp,
+            client_share: pending.client_share(&self.group),
+        };
+
+        let response = self.client.login(Request::new(login_req)).await?;
+        self.finish_login(Some(pending), &s, response.into_inner().into())
+    }
+
+    // Replace the registered key for x with the one for new_x, proving knowledge
+    // of x in a proof that also commits to the new key and its KDF parameters,
+    // and knowledge of new_x as registration does
+    pub async fn change_credentials(
+        &mut self,
+        user_id: &str,
+        x: &G::Scalar,
+        new_x: &G::Scalar,
+        new_kdf: &KdfParameters,
+    ) -> Result<(), AuthError> {
+        let (y1, y2) = protocol::public_key(&self.group, x);
+        let (new_y1, new_y2) = protocol::public_key(&self.group, new_x);
+        let k = self.group.random_scalar();
+        let (r1, r2) = protocol::commitment(&self.group, &k);
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        let c = protocol::change_credentials_challenge(
+            &self.group,
+            user_id,
+            (&y1, &y2),
+            (&r1, &r2),
+            (&new_y1, &new_y2),
+            Some(new_kdf),
+            &timestamp.to_be_bytes(),
+        );
+        let c = self.answered_challenge(c, None);
+        let s = protocol::response(&self.group, &k, &c, x);
+
+        let new_k = self.group.random_scalar();
+        let (new_r1, new_r2) = protocol::commitment(&self.group, &new_k);
+        let new_c = protocol::registration_challenge(&self.group, user_id, (&new_y1, &new_y2), (&new_r1, &new_r2), Some(new_kdf));
+        let new_s = protocol::response(&self.group, &new_k, &new_c, new_x);
+
+        let change_req = ChangeCredentialsRequest {
+            user: user_id.to_string(),
+            r1: self.group.encode_element(&r1),
+            r2: self.group.encode_element(&r2),
+            s: self.group.encode_scalar(&s),
+            timestamp,
+            new_y1: self.group.encode_element(&new_y1),
+            new_y2: self.group.encode_element(&new_y2),
+            kdf: Some(new_kdf.clone().into()),
+            new_r1: self.group.encode_element(&new_r1),
+            new_r2: self.group.encode_element(&new_r2),
+            new_s: self.group.encode_scalar(&new_s),
+        };
+
+        self.client.change_credentials(Request::new(change_req)).await?;
         Ok(())
     }
+
+    // Ask the server who a session token belongs to and when it expires
+    pub async fn validate_session(&mut self, session_id: &str) -> Result<(String, SystemTime), tonic::Status> {
+        let validate_req = ValidateSessionRequest {
+            session_id: session_id.to_string(),
+        };
+
+        let response = self.client.validate_session(Request::new(validate_req)).await?;
+        let validate_res = response.into_inner();
+
+        Ok((validate_res.user, UNIX_EPOCH + Duration::from_secs(validate_res.expires_at)))
+    }
 }
@@ -1,52 +1,115 @@
 // src/main.rs
 
-use pendersen_client::{load_parameters, read_input, pow, AuthServiceClient};
-use num_bigint::BigInt;
-use num_integer::Integer;
-use rand::Rng;
-use std::str::FromStr;
+use pendersen_client::{read_input, AuthError, AuthServiceClient, Session, TlsOptions};
+use pendersen_core::{protocol, Backend, Group, KdfParameters, ParameterSource, DEFAULT_PARAMETERS_PATH};
+
+// Server address; an https:// address connects with TLS
+const SERVER_VAR: &str = "PENDERSEN_SERVER";
+const DEFAULT_SERVER: &str = "http://[::1]:50051";
+
+// Set to 1 to agree on a session key with the server while logging in; needs
+// TLS or PENDERSEN_SERVER_PUBLIC_KEY
+const KEY_EXCHANGE_VAR: &str = "PENDERSEN_KEY_EXCHANGE";
+
+// The server's long-term public key, as it logs it at startup; when set, logins
+// the server cannot prove with that key are refused
+const SERVER_PUBLIC_KEY_VAR: &str = "PENDERSEN_SERVER_PUBLIC_KEY";
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let (p, q, g, h) = load_parameters();
+    // A built-in set name such as ffdhe3072, or the path of a parameter file
+    let source = ParameterSource::parse(&std::env::args().nth(1).unwrap_or_else(|| DEFAULT_PARAMETERS_PATH.to_string()));
+    let backend = source.load()?;
+    println!("Using {} group from {}", backend.name(), source);
 
-    let mut client = AuthServiceClient::connect("http://[::1]:50051".to_string()).await?;
+    match backend {
+        Backend::ModP(group) => run(group).await,
+        Backend::Ristretto255(group) => run(group).await,
+    }
+}
+
+async fn run<G: Group>(group: G) -> Result<(), Box<dyn std::error::Error>> {
+    let server = std::env::var(SERVER_VAR).unwrap_or_else(|_| DEFAULT_SERVER.to_string());
+    let client = if server.starts_with("https://") {
+        AuthServiceClient::connect_with_tls(server, group.clone(), &tls_options()).await?
+    } else {
+        AuthServiceClient::connect(server, group.clone()).await?
+    };
+    let mut client = client.with_key_exchange(std::env::var(KEY_EXCHANGE_VAR).is_ok_and(|value| value == "1"));
+    if let Ok(server_key) = std::env::var(SERVER_PUBLIC_KEY_VAR) {
+        let server_key = group
+            .decode_element(server_key.trim())
+            .map_err(|e| format!("{}: invalid server key: {}", SERVER_PUBLIC_KEY_VAR, e))?;
+        client = client.with_server_key(server_key);
+    }
 
     // Register User
     let user_id = read_input("Enter UserID: ");
-    let password_str = read_input("Enter Password: ");
-    let password = BigInt::from_str(&password_str).expect("Invalid password");
+    let password = read_input("Enter Password: ");
 
-    let y1 = pow(&g, &password, &p);
-    let y2 = pow(&h, &password, &p);
+    // Derive x from the password with a fresh per-user salt
+    let kdf = KdfParameters::generate();
+    let x = kdf.derive_secret(&group, &password)?;
 
-    client.register(&user_id, &y1, &y2).await?;
+    match client.register(&user_id, &x, &kdf).await {
+        Err(status) if status.code() == tonic::Code::AlreadyExists => println!("User {} is already registered", user_id),
+        result => result?,
+    }
 
     // Authentication Challenge
     let login = read_input("Do you want to login? (yes/no): ");
-    if login.to_lowercase() == "yes" {
-        let ran_k: i64 = rand::thread_rng().gen_range(1..10000);
-        let k = BigInt::from(ran_k);
+    if login.to_lowercase() == "yes" && read_input("Use single-request login? (yes/no): ").to_lowercase() == "yes" {
+        let reentered_password = read_input("Reenter Password: ");
+        let kdf = client.get_kdf_parameters(&user_id).await?;
 
-        let r1 = pow(&g, &k, &p);
-        let r2 = pow(&h, &k, &p);
+        report(client.login(&user_id, &kdf.derive_secret(&group, &reentered_password)?).await)?;
+    } else if login.to_lowercase() == "yes" {
+        // Fetch the salt and cost parameters before committing
+        let kdf = client.get_kdf_parameters(&user_id).await?;
+
+        let k = group.random_scalar();
+
+        let (r1, r2) = protocol::commitment(&group, &k);
 
         let (auth_id, c) = client.create_authentication_challenge(&user_id, &r1, &r2).await?;
 
         // Prompt the user to reenter the password for authentication
-        let reentered_password_str = read_input("Reenter Password: ");
-        let reentered_password = BigInt::from_str(&reentered_password_str).expect("Invalid password");
+        let reentered_password = read_input("Reenter Password: ");
 
-        // Calculate s = (k - c * reentered_password) % q, ensuring it's non-negative
-        let s_2 = &reentered_password * &c;
-        let s = (k - s_2).mod_floor(&q);
+        // Calculate s = (k - c * x) mod q with x derived from the reentered password
+        let s = protocol::response(&group, &k, &c, &kdf.derive_secret(&group, &reentered_password)?);
 
-        println!("Calculated s value: {}", s);
+        println!("Calculated s value: {}", group.encode_scalar(&s));
 
-        client.verify_authentication(&auth_id, &s).await?;
+        report(client.verify_authentication(&auth_id, &s).await)?;
     } else {
         println!("Exiting program.");
     }
 
     Ok(())
 }
+
+// PENDERSEN_TLS_CA, PENDERSEN_TLS_DOMAIN, and PENDERSEN_TLS_CERT with PENDERSEN_TLS_KEY for mutual TLS
+fn tls_options() -> TlsOptions {
+    let var = |name: &str| std::env::var_os(name).map(std::path::PathBuf::from);
+    TlsOptions {
+        ca_certificate: var("PENDERSEN_TLS_CA"),
+        domain_name: std::env::var("PENDERSEN_TLS_DOMAIN").ok(),
+        identity: var("PENDERSEN_TLS_CERT").zip(var("PENDERSEN_TLS_KEY")),
+    }
+}
+
+// A rejected proof is an expected outcome; anything else is still an error
+fn report(result: Result<Session, AuthError>) -> Result<(), AuthError> {
+    match result {
+        Ok(session) => {
+            println!("Authentication succeeded with session ID: {}", session.session_id);
+            if session.key.is_some() {
+                println!("Agreed on a session key with the server");
+            }
+        }
+        Err(AuthError::Unauthenticated(message)) => println!("Authentication failed: {}", message),
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use pendersen_core::pem;
+use pendersen_core::protocol::{CHANNEL_BINDING_EXPORTER_LABEL, CHANNEL_BINDING_LENGTH};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{ClientConfig, ProtocolVersion, Session},
+    webpki::DNSNameRef,
+};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::Service;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+// How to check a TLS server: a private root CA on top of the system roots, the
+// name its certificate must carry when it differs from the host in the URL, and
+// a client certificate and key for servers that require mutual TLS
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub ca_certificate: Option<PathBuf>,
+    pub domain_name: Option<String>,
+    pub identity: Option<(PathBuf, PathBuf)>,
+}
+
+impl TlsOptions {
+    // Read the PEM files into a rustls config. Only TLS 1.3 is offered, since the
+    // channel binding relies on its exporter.
+    pub fn client_config(&self) -> io::Result<ClientConfig> {
+        let mut config = ClientConfig::new();
+        config.root_store = match rustls_native_certs::load_native_certs() {
+            Ok(roots) | Err((Some(roots), _)) => roots,
+            Err((None, e)) => return Err(e),
+        };
+        if let Some(path) = &self.ca_certificate {
+            pem::add_roots(&mut config.root_store, path)?;
+        }
+        if let Some((cert, key)) = &self.identity {
+            config
+                .set_single_client_cert(pem::certificates(cert)?, pem::private_key(key)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        config.set_protocols(&[b"h2".to_vec()]);
+        config.versions = vec![ProtocolVersion::TLSv1_3];
+        Ok(config)
+    }
+
+    // Open a channel to an https:// address, verifying the server as these options
+    // say, along with the binding that follows the channel's current connection
+    pub async fn connect(&self, dst: String) -> Result<(Channel, ChannelBinding), Box<dyn std::error::Error>> {
+        let binding = ChannelBinding::default();
+        let connector = TlsConnector {
+            connector: Arc::new(self.client_config()?).into(),
+            domain_name: self.domain_name.clone(),
+            binding: binding.clone(),
+        };
+        let channel = Endpoint::from_shared(dst)?.connect_with_connector(connector).await?;
+        Ok((channel, binding))
+    }
+}
+
+// TLS exporter value of the connection a channel is currently using, updated
+// whenever it reconnects. Empty for plaintext channels.
+#[derive(Clone, Debug, Default)]
+pub struct ChannelBinding(Arc<Mutex<Option<[u8; CHANNEL_BINDING_LENGTH]>>>);
+
+impl ChannelBinding {
+    pub fn current(&self) -> Option<[u8; CHANNEL_BINDING_LENGTH]> {
+        *self.0.lock().unwrap()
+    }
+
+    fn export(&self, stream: &TlsStream<TcpStream>) -> io::Result<()> {
+        let mut binding = [0; CHANNEL_BINDING_LENGTH];
+        stream
+            .get_ref()
+            .1
+            .export_keying_material(&mut binding, CHANNEL_BINDING_EXPORTER_LABEL, None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        *self.0.lock().unwrap() = Some(binding);
+        Ok(())
+    }
+}
+
+// Connects over TCP and runs the TLS handshake itself, since tonic does not
+// expose the session it would otherwise create
+#[derive(Clone)]
+struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+    domain_name: Option<String>,
+    binding: ChannelBinding,
+}
+
+impl Service<Uri> for TlsConnector {
+    type Response = TlsStream<TcpStream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let this = self.clone();
+        Box::pin(async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "server address has no host"))?;
+            // IPv6 hosts keep their brackets in the URI
+            let address = host.trim_start_matches('[').trim_end_matches(']');
+            let stream = TcpStream::connect((address, uri.port_u16().unwrap_or(443))).await?;
+            stream.set_nodelay(true)?;
+
+            let name = this.domain_name.as_deref().unwrap_or(address);
+            let name = DNSNameRef::try_from_ascii_str(name).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a DNS name; set a domain name", name))
+            })?;
+            let stream = this.connector.connect(name, stream).await?;
+            this.binding.export(&stream)?;
+            Ok(stream)
+        })
+    }
+}
@@ -2,12 +2,14 @@ use pendersen_client::*;
 use pendersen_client::pb::{
     RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest,
     RegisterResponse, AuthenticationChallengeResponse, AuthenticationAnswerResponse,
+    LoginRequest, LoginResponse, KdfParametersRequest, KdfParametersResponse,
+    ValidateSessionRequest, ValidateSessionResponse, ChangeCredentialsRequest, ChangeCredentialsResponse,
 };
 use tonic::{Request, Response};
 use num_bigint::BigInt;
 use num_traits::Num; // Import the Num trait for from_str_radix
+use pendersen_core::{protocol, Group, KdfParameters, RistrettoParameters};
 use mockall::mock;
-use tokio;
 
 #[cfg(test)]
 mod tests {
@@ -22,6 +24,11 @@ mod tests {
                 req: Request<RegisterRequest>,
             ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<RegisterResponse>, tonic::Status>> + Send>>;
 
+            fn get_kdf_parameters(
+                &mut self,
+                req: Request<KdfParametersRequest>,
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<KdfParametersResponse>, tonic::Status>> + Send>>;
+
             fn create_authentication_challenge(
                 &mut self,
                 req: Request<AuthenticationChallengeRequest>,
@@ -31,6 +38,21 @@ mod tests {
                 &mut self,
                 req: Request<AuthenticationAnswerRequest>,
             ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<AuthenticationAnswerResponse>, tonic::Status>> + Send>>;
+
+            fn login(
+                &mut self,
+                req: Request<LoginRequest>,
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<LoginResponse>, tonic::Status>> + Send>>;
+
+            fn validate_session(
+                &mut self,
+                req: Request<ValidateSessionRequest>,
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ValidateSessionResponse>, tonic::Status>> + Send>>;
+
+            fn change_credentials(
+                &mut self,
+                req: Request<ChangeCredentialsRequest>,
+            ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ChangeCredentialsResponse>, tonic::Status>> + Send>>;
         }
     }
 
@@ -43,6 +65,13 @@ mod tests {
             self.register(req)
         }
 
+        fn get_kdf_parameters(
+            &mut self,
+            req: Request<KdfParametersRequest>,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<KdfParametersResponse>, tonic::Status>> + Send>> {
+            self.get_kdf_parameters(req)
+        }
+
         fn create_authentication_challenge(
             &mut self,
             req: Request<AuthenticationChallengeRequest>,
@@ -56,6 +85,27 @@ mod tests {
         ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<AuthenticationAnswerResponse>, tonic::Status>> + Send>> {
             self.verify_authentication(req)
         }
+
+        fn login(
+            &mut self,
+            req: Request<LoginRequest>,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<LoginResponse>, tonic::Status>> + Send>> {
+            self.login(req)
+        }
+
+        fn validate_session(
+            &mut self,
+            req: Request<ValidateSessionRequest>,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ValidateSessionResponse>, tonic::Status>> + Send>> {
+            self.validate_session(req)
+        }
+
+        fn change_credentials(
+            &mut self,
+            req: Request<ChangeCredentialsRequest>,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response<ChangeCredentialsResponse>, tonic::Status>> + Send>> {
+            self.change_credentials(req)
+        }
     }
 
     // Modify AuthServiceClient to be generic over the client type
@@ -74,6 +124,7 @@ mod tests {
                 user: user_id.to_string(),
                 y1: y1.to_string(),
                 y2: y2.to_string(),
+                ..Default::default()
             });
             self.client.register(request).await?;
             Ok(())
@@ -104,6 +155,7 @@ mod tests {
             let request = Request::new(AuthenticationAnswerRequest {
                 auth_id: auth_id.to_string(),
                 s: s.to_string(),
+                client_share: String::new(),
             });
             self.client.verify_authentication(request).await?;
             Ok(())
@@ -129,6 +181,38 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_register_sends_proof_of_knowledge() {
+        let group = RistrettoParameters::default();
+        let x = group.scalar_from_bigint(&BigInt::from(42));
+        let kdf = KdfParameters { salt: b"0123456789abcdef".to_vec(), memory_kib: 64, iterations: 1, parallelism: 1 };
+        let verifier = group.clone();
+
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // Check the proof the way the server does before storing the user
+        mock_client
+            .expect_register()
+            .withf(move |req: &Request<RegisterRequest>| {
+                let req = req.get_ref();
+                let y1 = verifier.decode_element(&req.y1).unwrap();
+                let y2 = verifier.decode_element(&req.y2).unwrap();
+                let r1 = verifier.decode_element(&req.r1).unwrap();
+                let r2 = verifier.decode_element(&req.r2).unwrap();
+                let s = verifier.decode_scalar(&req.s).unwrap();
+                let kdf = req.kdf.clone().map(KdfParameters::from);
+                let c = protocol::registration_challenge(&verifier, &req.user, (&y1, &y2), (&r1, &r2), kdf.as_ref());
+                protocol::verify(&verifier, (&y1, &y2), (&r1, &r2), &c, &s)
+            })
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Response::new(RegisterResponse { message: "User registered".to_string() })) }));
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group);
+
+        let result = auth_service_client.register("test_user", &x, &kdf).await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_create_authentication_challenge() {
         let mut mock_client = MockAuthClientTrait::new();
@@ -156,7 +240,7 @@ mod tests {
         mock_client
             .expect_verify_authentication()
             .withf(|req: &Request<AuthenticationAnswerRequest>| req.get_ref().auth_id == "auth123")
-            .returning(|_| Box::pin(async { Ok(Response::new(AuthenticationAnswerResponse { session_id: "session123".to_string() })) }));
+            .returning(|_| Box::pin(async { Ok(Response::new(AuthenticationAnswerResponse { session_id: "session123".to_string(), expires_at: 1_700_000_000, server_share: String::new(), server_proof: None })) }));
 
         let mut auth_service_client = AuthServiceClient { client: mock_client };
 
@@ -189,4 +273,246 @@ mod tests {
             assert_eq!(status.message(), "Bad password");
         }
     }
+
+    #[tokio::test]
+    async fn test_non_interactive_login() {
+        let group = RistrettoParameters::default();
+        let x = group.scalar_from_bigint(&BigInt::from(42));
+        let (y1, y2) = protocol::public_key(&group, &x);
+        let verifier = group.clone();
+
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // Check the proof the way the server does, recomputing c from the request
+        mock_client
+            .expect_login()
+            .withf(move |req: &Request<LoginRequest>| {
+                let req = req.get_ref();
+                let r1 = verifier.decode_element(&req.r1).unwrap();
+                let r2 = verifier.decode_element(&req.r2).unwrap();
+                let s = verifier.decode_scalar(&req.s).unwrap();
+                let c = protocol::login_challenge(&verifier, &req.user, (&y1, &y2), (&r1, &r2), &req.timestamp.to_be_bytes());
+                req.user == "test_user" && protocol::verify(&verifier, (&y1, &y2), (&r1, &r2), &c, &s)
+            })
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Response::new(LoginResponse { session_id: "session123".to_string(), expires_at: 1_700_000_000, server_share: String::new(), server_proof: None })) }));
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group);
+
+        let session = auth_service_client.login("test_user", &x).await.unwrap();
+        assert_eq!(session.session_id, "session123");
+    }
+
+    #[tokio::test]
+    async fn test_verify_authentication_returns_session() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        mock_client
+            .expect_verify_authentication()
+            .returning(|_| Box::pin(async {
+                Ok(Response::new(AuthenticationAnswerResponse { session_id: "session123".to_string(), expires_at: 1_700_000_000, server_share: String::new(), server_proof: None }))
+            }));
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, RistrettoParameters::default());
+
+        let session = auth_service_client.verify_authentication("auth123", &RistrettoParameters::default().random_scalar()).await.unwrap();
+        assert_eq!(session.session_id, "session123");
+        assert_eq!(session.expires_at, std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+    }
+
+    #[tokio::test]
+    async fn test_verify_authentication_surfaces_typed_errors() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        mock_client
+            .expect_verify_authentication()
+            .times(2)
+            .returning(|req| {
+                let status = if req.get_ref().auth_id == "expired" {
+                    tonic::Status::not_found("Session not found")
+                } else {
+                    tonic::Status::unauthenticated("Proof verification failed")
+                };
+                Box::pin(async move { Err(status) })
+            });
+
+        let group = RistrettoParameters::default();
+        let s = group.random_scalar();
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group);
+
+        let error = auth_service_client.verify_authentication("auth123", &s).await.unwrap_err();
+        assert!(matches!(error, AuthError::Unauthenticated(message) if message == "Proof verification failed"));
+
+        let error = auth_service_client.verify_authentication("expired", &s).await.unwrap_err();
+        assert!(matches!(error, AuthError::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_lockout_carries_retry_after() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        mock_client
+            .expect_verify_authentication()
+            .returning(|_| {
+                let mut status = tonic::Status::resource_exhausted("Too many failed attempts; retry in 30 seconds");
+                status.metadata_mut().insert("retry-after", "30".parse().unwrap());
+                Box::pin(async move { Err(status) })
+            });
+
+        let group = RistrettoParameters::default();
+        let s = group.random_scalar();
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group);
+
+        let error = auth_service_client.verify_authentication("auth123", &s).await.unwrap_err();
+        assert!(matches!(error, AuthError::ResourceExhausted { retry_after: Some(delay), .. } if delay == std::time::Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_key_exchange_requires_server_share() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // A server that ignores the client's share and answers as for a plain login
+        mock_client
+            .expect_login()
+            .withf(|req: &Request<LoginRequest>| !req.get_ref().client_share.is_empty())
+            .returning(|_| Box::pin(async { Ok(Response::new(LoginResponse { session_id: "session123".to_string(), expires_at: 1_700_000_000, server_share: String::new(), server_proof: None })) }));
+
+        let group = RistrettoParameters::default();
+        let x = group.random_scalar();
+        let server_key = group.exp(&group.g(), &group.random_scalar());
+        let mut auth_service_client =
+            pendersen_client::AuthServiceClient::new(mock_client, group).with_server_key(server_key).with_key_exchange(true);
+
+        let error = auth_service_client.login("test_user", &x).await.unwrap_err();
+        assert!(matches!(error, AuthError::KeyExchange(ref message) if message.contains("no key share")), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_server_key_requires_server_proof() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // A server that accepts any login but cannot prove who it is
+        mock_client
+            .expect_login()
+            .returning(|_| Box::pin(async { Ok(Response::new(LoginResponse { session_id: "session123".to_string(), expires_at: 1_700_000_000, server_share: String::new(), server_proof: None })) }));
+
+        let group = RistrettoParameters::default();
+        let x = group.random_scalar();
+        let server_key = group.exp(&group.g(), &group.random_scalar());
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group).with_server_key(server_key);
+
+        let error = auth_service_client.login("test_user", &x).await.unwrap_err();
+        assert!(matches!(error, AuthError::ServerAuthentication(_)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_session() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        mock_client
+            .expect_validate_session()
+            .withf(|req: &Request<ValidateSessionRequest>| req.get_ref().session_id == "session123")
+            .returning(|_| Box::pin(async {
+                Ok(Response::new(ValidateSessionResponse { user: "test_user".to_string(), expires_at: 1_700_000_000 }))
+            }));
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, RistrettoParameters::default());
+
+        let (user, expires_at) = auth_service_client.validate_session("session123").await.unwrap();
+        assert_eq!(user, "test_user");
+        assert_eq!(expires_at, std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000));
+    }
+
+    #[tokio::test]
+    async fn test_change_credentials_proves_old_secret() {
+        let group = RistrettoParameters::default();
+        let x = group.scalar_from_bigint(&BigInt::from(42));
+        let new_x = group.scalar_from_bigint(&BigInt::from(43));
+        let (y1, y2) = protocol::public_key(&group, &x);
+        let (new_y1, new_y2) = protocol::public_key(&group, &new_x);
+        let kdf = KdfParameters { salt: b"0123456789abcdef".to_vec(), memory_kib: 64, iterations: 1, parallelism: 1 };
+        let expected_kdf = kdf.clone();
+        let verifier = group.clone();
+
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // Check the proof the way the server does, against the old key
+        mock_client
+            .expect_change_credentials()
+            .withf(move |req: &Request<ChangeCredentialsRequest>| {
+                let req = req.get_ref();
+                let r1 = verifier.decode_element(&req.r1).unwrap();
+                let r2 = verifier.decode_element(&req.r2).unwrap();
+                let s = verifier.decode_scalar(&req.s).unwrap();
+                let kdf = req.kdf.clone().map(KdfParameters::from);
+                let c = protocol::change_credentials_challenge(
+                    &verifier,
+                    &req.user,
+                    (&y1, &y2),
+                    (&r1, &r2),
+                    (&new_y1, &new_y2),
+                    kdf.as_ref(),
+                    &req.timestamp.to_be_bytes(),
+                );
+                let new_r1 = verifier.decode_element(&req.new_r1).unwrap();
+                let new_r2 = verifier.decode_element(&req.new_r2).unwrap();
+                let new_s = verifier.decode_scalar(&req.new_s).unwrap();
+                let new_c = protocol::registration_challenge(&verifier, &req.user, (&new_y1, &new_y2), (&new_r1, &new_r2), kdf.as_ref());
+                kdf == Some(expected_kdf.clone())
+                    && req.new_y1 == verifier.encode_element(&new_y1)
+                    && protocol::verify(&verifier, (&y1, &y2), (&r1, &r2), &c, &s)
+                    && protocol::verify(&verifier, (&new_y1, &new_y2), (&new_r1, &new_r2), &new_c, &new_s)
+            })
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(Response::new(ChangeCredentialsResponse { message: "ok".to_string() })) }));
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, group);
+
+        let result = auth_service_client.change_credentials("test_user", &x, &new_x, &kdf).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_kdf_parameters() {
+        let kdf = KdfParameters {
+            salt: b"0123456789abcdef".to_vec(),
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let returned = kdf.clone();
+
+        let mut mock_client = MockAuthClientTrait::new();
+
+        mock_client
+            .expect_get_kdf_parameters()
+            .withf(|req: &Request<KdfParametersRequest>| req.get_ref().user == "test_user")
+            .returning(move |_| {
+                let kdf = returned.clone();
+                Box::pin(async move { Ok(Response::new(KdfParametersResponse { kdf: Some(kdf.into()) })) })
+            });
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, RistrettoParameters::default());
+
+        let result = auth_service_client.get_kdf_parameters("test_user").await.unwrap();
+        assert_eq!(result, kdf);
+    }
+
+    #[tokio::test]
+    async fn test_get_kdf_parameters_rejects_weak_salt() {
+        let mut mock_client = MockAuthClientTrait::new();
+
+        // A server handing out a trivial salt must not be trusted
+        mock_client
+            .expect_get_kdf_parameters()
+            .returning(|_| {
+                let kdf = KdfParameters { salt: vec![0], memory_kib: 64, iterations: 1, parallelism: 1 };
+                Box::pin(async move { Ok(Response::new(KdfParametersResponse { kdf: Some(kdf.into()) })) })
+            });
+
+        let mut auth_service_client = pendersen_client::AuthServiceClient::new(mock_client, RistrettoParameters::default());
+
+        let status = auth_service_client.get_kdf_parameters("test_user").await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
 }
@@ -0,0 +1,34 @@
+// Fixtures shared by the end-to-end tests; each test binary uses some of them
+#![allow(dead_code)]
+
+use pendersen_client::pb::auth_client::AuthClient;
+use pendersen_client::AuthServiceClient;
+use pendersen_core::{Group, KdfParameters, RistrettoParameters};
+use pendersen_server::pb::auth_server::AuthServer;
+use pendersen_server::AuthService;
+
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+
+pub type Client = AuthServiceClient<AuthClient<Channel>, RistrettoParameters>;
+pub type Scalar = <RistrettoParameters as Group>::Scalar;
+
+// Serve `auth_service` on a loopback port, returning its address
+pub async fn serve(auth_service: AuthService<RistrettoParameters>) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::new(auth_service))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    format!("http://{}", addr)
+}
+
+// Connect and register test_user, returning the client and its secret
+pub async fn registered_client(addr: String) -> (Client, Scalar) {
+    let mut client = AuthServiceClient::connect(addr, RistrettoParameters::default()).await.unwrap();
+    let x = client.group().random_scalar();
+    client.register("test_user", &x, &KdfParameters::generate()).await.unwrap();
+    (client, x)
+}
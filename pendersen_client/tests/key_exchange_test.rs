@@ -0,0 +1,96 @@
+use pendersen_client::AuthError;
+use pendersen_core::{protocol, Group, RistrettoParameters, ServerKey};
+use pendersen_server::session_registry::DEFAULT_SESSION_TTL;
+use pendersen_server::{AuthService, MemorySessionStore, SessionRegistry, SessionStore};
+
+use std::sync::Arc;
+
+mod common;
+use common::{registered_client, serve};
+
+type Element = <RistrettoParameters as Group>::Element;
+
+// A fresh AuthService with a server key, that key for the client to pin, and a
+// registry that reads the sessions it issues the way another service sharing
+// its store would
+fn auth_service() -> (AuthService<RistrettoParameters>, Element, SessionRegistry) {
+    let group = RistrettoParameters::default();
+    let server_key = ServerKey::generate(&group);
+    let pinned = *server_key.public();
+    let sessions: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::new());
+    let auth_service = AuthService::new(group).with_session_store(sessions.clone()).with_server_key(server_key);
+    (auth_service, pinned, SessionRegistry::new(sessions, DEFAULT_SESSION_TTL))
+}
+
+#[tokio::test]
+async fn test_interactive_login_agrees_on_a_key() {
+    // Arrange
+    let (auth_service, pinned, sessions) = auth_service();
+    let (client, x) = registered_client(serve(auth_service).await).await;
+    let mut client = client.with_server_key(pinned).with_key_exchange(true);
+    let group = client.group().clone();
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+
+    // Act
+    let (auth_id, c) = client.create_authentication_challenge("test_user", &r1, &r2).await.unwrap();
+    let session = client
+        .verify_authentication(&auth_id, &protocol::response(&group, &k, &c, &x))
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(session.key.is_some());
+    let stored = sessions.validate(&session.session_id).unwrap().unwrap();
+    assert_eq!(stored.key, session.key);
+}
+
+#[tokio::test]
+async fn test_non_interactive_login_agrees_on_a_fresh_key_each_time() {
+    // Arrange
+    let (auth_service, pinned, sessions) = auth_service();
+    let (client, x) = registered_client(serve(auth_service).await).await;
+    let mut client = client.with_server_key(pinned).with_key_exchange(true);
+
+    // Act
+    let first = client.login("test_user", &x).await.unwrap();
+    let second = client.login("test_user", &x).await.unwrap();
+
+    // Assert
+    assert!(first.key.is_some());
+    assert_eq!(sessions.validate(&first.session_id).unwrap().unwrap().key, first.key);
+    assert_eq!(sessions.validate(&second.session_id).unwrap().unwrap().key, second.key);
+    assert_ne!(first.key, second.key);
+}
+
+#[tokio::test]
+async fn test_key_exchange_is_off_by_default() {
+    // Arrange
+    let (auth_service, _, sessions) = auth_service();
+    let (mut client, x) = registered_client(serve(auth_service).await).await;
+
+    // Act
+    let session = client.login("test_user", &x).await.unwrap();
+
+    // Assert
+    assert_eq!(session.key, None);
+    assert_eq!(sessions.validate(&session.session_id).unwrap().unwrap().key, None);
+}
+
+#[tokio::test]
+async fn test_key_exchange_needs_tls_or_a_pinned_server_key() {
+    // Arrange: plaintext, and no key to check the server's share against
+    let (auth_service, _, _) = auth_service();
+    let (client, x) = registered_client(serve(auth_service).await).await;
+    let mut client = client.with_key_exchange(true);
+    let group = client.group().clone();
+    let (r1, r2) = protocol::commitment(&group, &group.random_scalar());
+
+    // Act
+    let challenge = client.create_authentication_challenge("test_user", &r1, &r2).await;
+    let login = client.login("test_user", &x).await;
+
+    // Assert
+    assert_eq!(challenge.unwrap_err().code(), tonic::Code::FailedPrecondition);
+    assert!(matches!(login, Err(AuthError::KeyExchange(_))), "{:?}", login);
+}
@@ -0,0 +1,59 @@
+use pendersen_client::AuthError;
+use pendersen_core::{protocol, Group, RistrettoParameters, ServerKey};
+use pendersen_server::AuthService;
+
+mod common;
+use common::{registered_client, serve};
+
+#[tokio::test]
+async fn test_server_proves_the_pinned_key() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let server_key = ServerKey::generate(&group);
+    let pinned = *server_key.public();
+    let auth_service = AuthService::new(group.clone()).with_server_key(server_key);
+    let (client, x) = registered_client(serve(auth_service).await).await;
+    let mut client = client.with_server_key(pinned);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+
+    // Act
+    let (auth_id, c) = client.create_authentication_challenge("test_user", &r1, &r2).await.unwrap();
+    let interactive = client.verify_authentication(&auth_id, &protocol::response(&group, &k, &c, &x)).await;
+    let non_interactive = client.with_key_exchange(true).login("test_user", &x).await;
+
+    // Assert
+    assert!(interactive.is_ok(), "{:?}", interactive);
+    assert!(non_interactive.unwrap().key.is_some());
+}
+
+#[tokio::test]
+async fn test_impostor_server_is_refused() {
+    // Arrange: the server at the address holds a different key than the one pinned
+    let group = RistrettoParameters::default();
+    let pinned = *ServerKey::generate(&group).public();
+    let auth_service = AuthService::new(group.clone()).with_server_key(ServerKey::generate(&group));
+    let (client, x) = registered_client(serve(auth_service).await).await;
+    let mut client = client.with_server_key(pinned);
+
+    // Act
+    let error = client.login("test_user", &x).await.unwrap_err();
+
+    // Assert
+    assert!(matches!(error, AuthError::ServerAuthentication(_)), "{}", error);
+}
+
+#[tokio::test]
+async fn test_server_without_a_key_is_refused_when_one_is_pinned() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let pinned = *ServerKey::generate(&group).public();
+    let (client, x) = registered_client(serve(AuthService::new(group.clone())).await).await;
+    let mut client = client.with_server_key(pinned);
+
+    // Act
+    let error = client.login("test_user", &x).await.unwrap_err();
+
+    // Assert
+    assert!(matches!(error, AuthError::ServerAuthentication(_)), "{}", error);
+}
@@ -0,0 +1,287 @@
+use pendersen_client::pb::auth_client::AuthClient;
+use pendersen_client::pb::{AuthenticationAnswerRequest, AuthenticationChallengeRequest};
+use pendersen_client::{AuthServiceClient, TlsOptions};
+use pendersen_core::{protocol, Group, KdfParameters, RistrettoParameters};
+use pendersen_server::config::TlsConfig;
+use pendersen_server::pb::auth_server::AuthServer;
+use pendersen_server::{tls_incoming, AuthService};
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use tonic::transport::{Channel, Server};
+
+type Client = AuthServiceClient<AuthClient<Channel>, RistrettoParameters>;
+
+// The server certificate names this host, not the loopback address we connect to
+const SERVER_NAME: &str = "auth.pendersen.test";
+
+fn certificate_authority(name: &str) -> Certificate {
+    let mut params = CertificateParams::new(Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name = DistinguishedName::new();
+    params.distinguished_name.push(DnType::CommonName, name);
+    Certificate::from_params(params).unwrap()
+}
+
+// Write a certificate for `name` signed by `ca` and its key, returning both paths
+fn issue(dir: &Path, ca: &Certificate, name: &str) -> (PathBuf, PathBuf) {
+    let cert = Certificate::from_params(CertificateParams::new(vec![name.to_string()])).unwrap();
+    let cert_path = dir.join(format!("{}.pem", name));
+    let key_path = dir.join(format!("{}.key", name));
+    fs::write(&cert_path, cert.serialize_pem_with_signer(ca).unwrap()).unwrap();
+    fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+    (cert_path, key_path)
+}
+
+fn write_ca(dir: &Path, ca: &Certificate, file: &str) -> PathBuf {
+    let path = dir.join(file);
+    fs::write(&path, ca.serialize_pem().unwrap()).unwrap();
+    path
+}
+
+struct Pki {
+    dir: TempDir,
+    ca: Certificate,
+    ca_path: PathBuf,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let ca = certificate_authority("pendersen test CA");
+        let ca_path = write_ca(dir.path(), &ca, "ca.pem");
+        Pki { dir, ca, ca_path }
+    }
+
+    fn server_tls(&self, require_client_cert: bool) -> TlsConfig {
+        let (cert, key) = issue(self.dir.path(), &self.ca, SERVER_NAME);
+        TlsConfig {
+            cert,
+            key,
+            client_ca: require_client_cert.then(|| self.ca_path.clone()),
+        }
+    }
+
+    fn client_options(&self) -> TlsOptions {
+        TlsOptions {
+            ca_certificate: Some(self.ca_path.clone()),
+            domain_name: Some(SERVER_NAME.to_string()),
+            identity: None,
+        }
+    }
+}
+
+// Serve a fresh AuthService over TLS on a loopback port and return its https:// address
+async fn serve(tls: &TlsConfig) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let incoming = tls_incoming(listener, tls.load().unwrap());
+    tokio::spawn(
+        Server::builder()
+            .add_service(AuthServer::new(AuthService::new(RistrettoParameters::default())))
+            .serve_with_incoming(incoming),
+    );
+    format!("https://{}", addr)
+}
+
+// Register and log in over `client`, proving the channel carries the whole protocol
+async fn register_and_login(client: &mut Client) -> Result<(), Box<dyn std::error::Error>> {
+    let x = client.group().random_scalar();
+    client.register("test_user", &x, &KdfParameters::generate()).await?;
+    let session = client.login("test_user", &x).await?;
+    assert!(!session.session_id.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_login_over_tls() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await;
+
+    // Act
+    let mut client = AuthServiceClient::connect_with_tls(addr, RistrettoParameters::default(), &pki.client_options())
+        .await
+        .unwrap();
+
+    // Assert
+    register_and_login(&mut client).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_key_exchange_over_tls_needs_no_pinned_key() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await;
+    let client = AuthServiceClient::connect_with_tls(addr, RistrettoParameters::default(), &pki.client_options())
+        .await
+        .unwrap();
+    let mut client = client.with_key_exchange(true);
+    let x = client.group().random_scalar();
+    client.register("test_user", &x, &KdfParameters::generate()).await.unwrap();
+
+    // Act
+    let session = client.login("test_user", &x).await;
+
+    // Assert
+    assert!(session.unwrap().key.is_some());
+}
+
+#[tokio::test]
+async fn test_server_from_an_unknown_ca_is_refused() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await;
+    let other = certificate_authority("someone else");
+    let options = TlsOptions {
+        ca_certificate: Some(write_ca(pki.dir.path(), &other, "other-ca.pem")),
+        ..pki.client_options()
+    };
+
+    // Act
+    let result = AuthServiceClient::connect_with_tls(addr, RistrettoParameters::default(), &options).await;
+
+    // Assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_server_name_must_match_certificate() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await;
+    let options = TlsOptions {
+        domain_name: Some("elsewhere.pendersen.test".to_string()),
+        ..pki.client_options()
+    };
+
+    // Act
+    let result = AuthServiceClient::connect_with_tls(addr, RistrettoParameters::default(), &options).await;
+
+    // Assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_plaintext_client_cannot_talk_to_tls_server() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await.replacen("https://", "http://", 1);
+
+    // Act: a plaintext HTTP/2 connection is refused during the handshake or on first use
+    let result = match AuthServiceClient::connect(addr, RistrettoParameters::default()).await {
+        Ok(mut client) => register_and_login(&mut client).await,
+        Err(e) => Err(e),
+    };
+
+    // Assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_mutual_tls_accepts_client_certificate() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(true)).await;
+    let options = TlsOptions {
+        identity: Some(issue(pki.dir.path(), &pki.ca, "client")),
+        ..pki.client_options()
+    };
+
+    // Act
+    let mut client = AuthServiceClient::connect_with_tls(addr, RistrettoParameters::default(), &options)
+        .await
+        .unwrap();
+
+    // Assert
+    register_and_login(&mut client).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_mutual_tls_rejects_missing_or_foreign_client_certificate() {
+    // Arrange
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(true)).await;
+    let other = certificate_authority("someone else");
+    let foreign = TlsOptions {
+        identity: Some(issue(pki.dir.path(), &other, "intruder")),
+        ..pki.client_options()
+    };
+
+    for options in [pki.client_options(), foreign] {
+        // Act: TLS 1.3 may only report the rejected certificate on the first request
+        let result = match AuthServiceClient::connect_with_tls(addr.clone(), RistrettoParameters::default(), &options).await {
+            Ok(mut client) => register_and_login(&mut client).await,
+            Err(e) => Err(e),
+        };
+
+        // Assert
+        assert!(result.is_err(), "{:?} was accepted", options.identity);
+    }
+}
+
+#[tokio::test]
+async fn test_answer_relayed_between_tls_sessions_is_rejected() {
+    // Arrange: the victim has registered and holds its own TLS session
+    let pki = Pki::new();
+    let addr = serve(&pki.server_tls(false)).await;
+    let group = RistrettoParameters::default();
+    let mut victim = AuthServiceClient::connect_with_tls(addr.clone(), group.clone(), &pki.client_options())
+        .await
+        .unwrap();
+    let x = group.random_scalar();
+    victim.register("test_user", &x, &KdfParameters::generate()).await.unwrap();
+    let (victim_channel, victim_binding) = pki.client_options().connect(addr.clone()).await.unwrap();
+    let (attacker_channel, _) = pki.client_options().connect(addr).await.unwrap();
+
+    // The attacker forwards a challenge from its session, the victim answers it
+    // for the session it has with what it believes is the server, and the
+    // attacker sends that answer on
+    let relay = |mut channel: AuthClient<Channel>| {
+        let group = group.clone();
+        let binding = victim_binding.current().unwrap();
+        async move {
+            let k = group.random_scalar();
+            let (r1, r2) = protocol::commitment(&group, &k);
+            let challenge = channel
+                .create_authentication_challenge(AuthenticationChallengeRequest {
+                    user: "test_user".to_string(),
+                    r1: group.encode_element(&r1),
+                    r2: group.encode_element(&r2),
+                })
+                .await?
+                .into_inner();
+            let c = group.decode_scalar(&challenge.c).unwrap();
+            let s = protocol::response(&group, &k, &protocol::bind_challenge(&group, &c, &binding), &x);
+            channel
+                .verify_authentication(AuthenticationAnswerRequest {
+                    auth_id: challenge.auth_id,
+                    s: group.encode_scalar(&s),
+                    client_share: String::new(),
+                })
+                .await
+        }
+    };
+
+    // Act
+    let relayed = relay(AuthClient::new(attacker_channel)).await;
+
+    // Assert
+    assert_eq!(relayed.unwrap_err().code(), tonic::Code::Unauthenticated);
+    // The same answer goes through on the session it was computed for
+    relay(AuthClient::new(victim_channel)).await.unwrap();
+}
+
+#[test]
+fn test_missing_certificate_file_is_reported() {
+    let options = TlsOptions {
+        ca_certificate: Some(PathBuf::from("/nonexistent/ca.pem")),
+        ..TlsOptions::default()
+    };
+
+    let error = options.client_config().err().unwrap();
+
+    assert!(error.to_string().contains("/nonexistent/ca.pem"));
+}
@@ -0,0 +1,110 @@
+use serde::Deserialize;
+use std::{error::Error, fmt, fs, path::{Path, PathBuf}};
+
+use crate::params::GroupParameters;
+use crate::ristretto::RistrettoParameters;
+use crate::standard;
+
+// Only the group name is read here; the rest of the file belongs to the group
+#[derive(Deserialize)]
+struct GroupSelector {
+    #[serde(default)]
+    group: Option<String>,
+}
+
+// The group a deployment runs the protocol in, as chosen by its parameter
+// file. Binaries match on this once at startup and run generically over the
+// selected `Group` implementation from then on.
+#[derive(Clone, Debug)]
+pub enum Backend {
+    ModP(GroupParameters),
+    Ristretto255(RistrettoParameters),
+}
+
+impl Backend {
+    // Parse a parameter file; files without a "group" field describe a mod-p group
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let selector: GroupSelector = serde_json::from_str(json)?;
+        match selector.group.as_deref() {
+            None | Some("modp") => Ok(Backend::ModP(GroupParameters::from_json(json)?)),
+            Some(RISTRETTO255) => Ok(Backend::Ristretto255(RistrettoParameters::default())),
+            Some(other) => Err(format!("unknown group {:?}", other).into()),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        Self::from_json(&file_content)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::ModP(_) => "modp",
+            Backend::Ristretto255(_) => RISTRETTO255,
+        }
+    }
+}
+
+impl From<GroupParameters> for Backend {
+    fn from(params: GroupParameters) -> Self {
+        Backend::ModP(params)
+    }
+}
+
+impl From<RistrettoParameters> for Backend {
+    fn from(params: RistrettoParameters) -> Self {
+        Backend::Ristretto255(params)
+    }
+}
+
+// Where a deployment's group comes from: a built-in named set or a parameter file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParameterSource {
+    Standard(&'static str),
+    File(PathBuf),
+}
+
+impl ParameterSource {
+    // Names of built-in sets win over file paths
+    pub fn parse(value: &str) -> Self {
+        if value == RISTRETTO255 {
+            return ParameterSource::Standard(RISTRETTO255);
+        }
+        match standard::find(value) {
+            Some(set) => ParameterSource::Standard(set.name),
+            None => ParameterSource::File(PathBuf::from(value)),
+        }
+    }
+
+    pub fn load(&self) -> Result<Backend, Box<dyn Error>> {
+        match self {
+            ParameterSource::Standard(RISTRETTO255) => Ok(Backend::Ristretto255(RistrettoParameters::default())),
+            ParameterSource::Standard(name) => standard::find(name)
+                .map(|set| Backend::ModP(set.parameters()))
+                .ok_or_else(|| format!("unknown parameter set {:?}", name).into()),
+            ParameterSource::File(path) => Backend::load(path),
+        }
+    }
+
+    // Names accepted by `parse` as built-in sets
+    pub fn standard_names() -> impl Iterator<Item = &'static str> {
+        standard::STANDARD_SETS.iter().map(|set| set.name).chain([RISTRETTO255])
+    }
+}
+
+impl fmt::Display for ParameterSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterSource::Standard(RISTRETTO255) => write!(f, "built-in set {}", RISTRETTO255),
+            ParameterSource::Standard(name) => {
+                let reference = standard::find(name).map(|set| set.reference).unwrap_or("unknown");
+                write!(f, "built-in set {} ({})", name, reference)
+            }
+            ParameterSource::File(path) => write!(f, "parameter file {}", path.display()),
+        }
+    }
+}
+
+const RISTRETTO255: &str = "ristretto255";
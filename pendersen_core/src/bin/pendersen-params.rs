@@ -0,0 +1,65 @@
+// Generates or audits safe-prime parameter files.
+//
+//   pendersen-params [--bits N] [--seed HEX] [--out PATH]
+//   pendersen-params --verify PATH
+
+use pendersen_core::generate::{audit, generate, GeneratedParameterFile};
+use rand::RngCore;
+use std::{env, error::Error, fs, process};
+
+const DEFAULT_BITS: u64 = 2048;
+
+fn main() {
+    if let Err(e) = run(env::args().skip(1).collect()) {
+        eprintln!("pendersen-params: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut bits = DEFAULT_BITS;
+    let mut seed = None;
+    let mut out = None;
+    let mut verify = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--bits" => bits = value()?.parse()?,
+            "--seed" => seed = Some(hex::decode(value()?)?),
+            "--out" => out = Some(value()?),
+            "--verify" => verify = Some(value()?),
+            "--help" | "-h" => {
+                println!("usage: pendersen-params [--bits N] [--seed HEX] [--out PATH]");
+                println!("       pendersen-params --verify PATH");
+                return Ok(());
+            }
+            other => return Err(format!("unknown argument {:?}", other).into()),
+        }
+    }
+
+    if let Some(path) = verify {
+        let file: GeneratedParameterFile = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        audit(&file)?;
+        println!("{}: parameters match seed {}", path, file.generation.seed);
+        return Ok(());
+    }
+
+    let seed = seed.unwrap_or_else(|| {
+        let mut seed = vec![0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        seed
+    });
+    let file = generate(bits, &seed)?;
+    let json = serde_json::to_string_pretty(&file)?;
+
+    match out {
+        Some(path) => {
+            fs::write(&path, json + "\n")?;
+            eprintln!("wrote {}-bit parameters to {}", bits, path);
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::One;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use std::error::Error;
+
+use crate::params::GroupParameters;
+use crate::primes::{has_small_factor, is_probable_prime, small_primes, DEFAULT_ROUNDS};
+
+// Identifies the derivation below; bump it whenever the derivation changes
+pub const GENERATION_METHOD: &str = "pendersen-params/v1 sha512 safe-prime";
+
+// How a parameter set was produced, stored next to p, q, g and h so that
+// anyone can re-derive the group from the seed and compare
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Generation {
+    pub method: String,
+    pub seed: String,
+    pub bits: u64,
+    pub q_counter: u64,
+}
+
+// Layout of a generated parameter file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeneratedParameterFile {
+    pub p: String,
+    pub q: String,
+    pub g: String,
+    pub h: String,
+    pub generation: Generation,
+}
+
+impl GeneratedParameterFile {
+    pub fn parameters(&self) -> Result<GroupParameters, Box<dyn Error>> {
+        GroupParameters::from_json(&serde_json::to_string(self)?)
+    }
+}
+
+// Generate a group with a `bits`-bit safe prime p = 2q + 1. Both q and the
+// generators are derived from `seed`: q is the first safe-prime candidate in
+// the hash sequence and g, h are hashed into the order-q subgroup, so nobody
+// knows log_g h.
+pub fn generate(bits: u64, seed: &[u8]) -> Result<GeneratedParameterFile, Box<dyn Error>> {
+    if bits < 16 {
+        return Err(format!("a {}-bit modulus is too small", bits).into());
+    }
+
+    let primes = small_primes();
+    let mut q_counter = 0u64;
+    let q = loop {
+        let q = q_candidate(seed, bits, q_counter);
+        let p: BigInt = 2 * &q + 1;
+        if !has_small_factor(&q, &primes)
+            && !has_small_factor(&p, &primes)
+            && is_probable_prime(&q, DEFAULT_ROUNDS)
+            && is_probable_prime(&p, DEFAULT_ROUNDS)
+        {
+            break q;
+        }
+        q_counter += 1;
+    };
+    let p: BigInt = 2 * &q + 1;
+    let g = hash_to_subgroup(seed, b"g", &p);
+    let h = hash_to_subgroup(seed, b"h", &p);
+
+    Ok(GeneratedParameterFile {
+        p: p.to_string(),
+        q: q.to_string(),
+        g: g.to_string(),
+        h: h.to_string(),
+        generation: Generation {
+            method: GENERATION_METHOD.to_string(),
+            seed: hex::encode(seed),
+            bits,
+            q_counter,
+        },
+    })
+}
+
+// Re-derive a generated parameter file from its recorded seed and check
+// that every value matches
+pub fn audit(file: &GeneratedParameterFile) -> Result<(), Box<dyn Error>> {
+    let generation = &file.generation;
+    if generation.method != GENERATION_METHOD {
+        return Err(format!("unsupported generation method {:?}", generation.method).into());
+    }
+    let seed = hex::decode(&generation.seed)?;
+    let params = file.parameters()?;
+
+    let q = q_candidate(&seed, generation.bits, generation.q_counter);
+    if q != params.q {
+        return Err("q does not match the candidate derived from the seed".into());
+    }
+    let p: BigInt = 2 * &q + 1;
+    if p != params.p {
+        return Err("p is not 2q + 1".into());
+    }
+    if !is_probable_prime(&q, DEFAULT_ROUNDS) || !is_probable_prime(&p, DEFAULT_ROUNDS) {
+        return Err("p = 2q + 1 is not a safe prime".into());
+    }
+    if hash_to_subgroup(&seed, b"g", &p) != params.g {
+        return Err("g does not match the value derived from the seed".into());
+    }
+    if hash_to_subgroup(&seed, b"h", &p) != params.h {
+        return Err("h does not match the value derived from the seed".into());
+    }
+    Ok(())
+}
+
+// The `counter`-th (bits - 1)-bit odd candidate for q
+fn q_candidate(seed: &[u8], bits: u64, counter: u64) -> BigInt {
+    let mut label = b"q".to_vec();
+    label.extend_from_slice(&counter.to_be_bytes());
+    let q_bits = bits - 1;
+    let mut q = expand(seed, &label, q_bits) >> (expanded_bits(q_bits) - q_bits);
+    q |= BigInt::one() << (q_bits - 1);
+    q |= BigInt::one();
+    q
+}
+
+// Hash into the order-q subgroup of Z_p^* by squaring a hashed value; for a
+// safe prime the squares are exactly that subgroup
+pub fn hash_to_subgroup(seed: &[u8], label: &[u8], p: &BigInt) -> BigInt {
+    let bits = p.bits();
+    for counter in 0u64.. {
+        let mut input = label.to_vec();
+        input.extend_from_slice(&counter.to_be_bytes());
+        // 128 extra bits make the reduction mod p statistically uniform
+        let value = expand(seed, &input, bits + 128).mod_floor(p);
+        let element = value.modpow(&BigInt::from(2), p);
+        if element > BigInt::one() {
+            return element;
+        }
+    }
+    unreachable!("hash_to_subgroup exhausted its counter")
+}
+
+// Whole number of SHA-512 blocks needed for `bits` bits
+fn expanded_bits(bits: u64) -> u64 {
+    bits.div_ceil(512) * 512
+}
+
+// SHA-512 in counter mode over (seed, label), at least `bits` bits long
+fn expand(seed: &[u8], label: &[u8], bits: u64) -> BigInt {
+    let mut output = Vec::new();
+    for block in 0..expanded_bits(bits) / 512 {
+        let mut hasher = Sha512::new();
+        hasher.update(GENERATION_METHOD.as_bytes());
+        for part in [seed, label] {
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part);
+        }
+        hasher.update(block.to_be_bytes());
+        output.extend_from_slice(&hasher.finalize());
+    }
+    BigInt::from_bytes_be(Sign::Plus, &output)
+}
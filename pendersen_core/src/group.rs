@@ -0,0 +1,56 @@
+use num_bigint::BigInt;
+use std::{error::Error, fmt};
+
+// A prime-order group the Chaum-Pedersen protocol can run in. Implementations
+// own the two generators, arithmetic on elements and on scalars mod the group
+// order, and the string encoding used on the wire.
+pub trait Group: Clone + Send + Sync + 'static {
+    type Element: Clone + PartialEq + fmt::Debug + Send + Sync;
+    type Scalar: Clone + PartialEq + fmt::Debug + Send + Sync;
+
+    // Short name reported in logs and parameter files
+    fn name(&self) -> &'static str;
+
+    // Public values identifying this group instance, bound into Fiat-Shamir transcripts
+    fn transcript_parameters(&self) -> Vec<String>;
+
+    // Order q of the group; every scalar lives in [0, q)
+    fn order(&self) -> BigInt;
+
+    fn g(&self) -> Self::Element;
+    fn h(&self) -> Self::Element;
+    fn identity(&self) -> Self::Element;
+
+    // Whether `element` lies in the order-q group
+    fn is_member(&self, element: &Self::Element) -> bool;
+
+    // Group operation and exponentiation, written multiplicatively
+    fn op(&self, a: &Self::Element, b: &Self::Element) -> Self::Element;
+    fn exp(&self, base: &Self::Element, exponent: &Self::Scalar) -> Self::Element;
+
+    // Scalar arithmetic mod q
+    fn scalar_from_bigint(&self, value: &BigInt) -> Self::Scalar;
+    fn scalar_to_bigint(&self, scalar: &Self::Scalar) -> BigInt;
+    fn scalar_add(&self, a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    fn scalar_sub(&self, a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    fn scalar_mul(&self, a: &Self::Scalar, b: &Self::Scalar) -> Self::Scalar;
+    // Uniform scalar in [0, q) drawn from the operating system's CSPRNG
+    fn random_scalar(&self) -> Self::Scalar;
+
+    fn encode_element(&self, element: &Self::Element) -> String;
+    fn decode_element(&self, value: &str) -> Result<Self::Element, DecodeError>;
+    fn encode_scalar(&self, scalar: &Self::Scalar) -> String;
+    fn decode_scalar(&self, value: &str) -> Result<Self::Scalar, DecodeError>;
+}
+
+// Raised when a string received from the wire is not a valid encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(pub String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for DecodeError {}
@@ -0,0 +1,95 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use num_bigint::{BigInt, Sign};
+use rand::RngCore;
+use std::{error::Error, fmt};
+
+use crate::group::Group;
+
+pub const SALT_LENGTH: usize = 16;
+pub const DEFAULT_MEMORY_KIB: u32 = Params::DEFAULT_M_COST;
+pub const DEFAULT_ITERATIONS: u32 = Params::DEFAULT_T_COST;
+pub const DEFAULT_PARALLELISM: u32 = Params::DEFAULT_P_COST;
+
+// Bounds accepted from the other side, so a peer cannot make us use a
+// trivial salt or burn unbounded memory
+const MIN_SALT_LENGTH: usize = 8;
+const MAX_SALT_LENGTH: usize = 64;
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+const MAX_ITERATIONS: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+
+// Bytes of Argon2id output reduced into the secret exponent
+const OUTPUT_LENGTH: usize = 64;
+
+// Per-user Argon2id settings turning a password into the secret x. The
+// server stores them at registration and hands them back before login.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KdfParameters {
+    pub salt: Vec<u8>,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfParameters {
+    // Fresh random salt with the default cost parameters
+    pub fn generate() -> Self {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        KdfParameters {
+            salt,
+            memory_kib: DEFAULT_MEMORY_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), KdfError> {
+        if !(MIN_SALT_LENGTH..=MAX_SALT_LENGTH).contains(&self.salt.len()) {
+            return Err(KdfError(format!(
+                "salt must be between {} and {} bytes, got {}",
+                MIN_SALT_LENGTH,
+                MAX_SALT_LENGTH,
+                self.salt.len()
+            )));
+        }
+        if !(1..=MAX_PARALLELISM).contains(&self.parallelism) {
+            return Err(KdfError(format!("parallelism must be between 1 and {}", MAX_PARALLELISM)));
+        }
+        if !(8 * self.parallelism..=MAX_MEMORY_KIB).contains(&self.memory_kib) {
+            return Err(KdfError(format!(
+                "memory must be between {} and {} KiB",
+                8 * self.parallelism,
+                MAX_MEMORY_KIB
+            )));
+        }
+        if !(1..=MAX_ITERATIONS).contains(&self.iterations) {
+            return Err(KdfError(format!("iterations must be between 1 and {}", MAX_ITERATIONS)));
+        }
+        Ok(())
+    }
+
+    // x = Argon2id(password, salt) reduced mod the group order
+    pub fn derive_secret<G: Group>(&self, group: &G, password: &str) -> Result<G::Scalar, KdfError> {
+        self.validate()?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(OUTPUT_LENGTH))
+            .map_err(|e| KdfError(e.to_string()))?;
+        let mut output = [0u8; OUTPUT_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &self.salt, &mut output)
+            .map_err(|e| KdfError(e.to_string()))?;
+        Ok(group.scalar_from_bigint(&BigInt::from_bytes_be(Sign::Plus, &output)))
+    }
+}
+
+// Raised for out-of-bounds KDF settings or a failing Argon2 computation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfError(pub String);
+
+impl fmt::Display for KdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid key derivation parameters: {}", self.0)
+    }
+}
+
+impl Error for KdfError {}
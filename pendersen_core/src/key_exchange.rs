@@ -0,0 +1,90 @@
+use hkdf::Hkdf;
+use sha2::Sha512;
+use std::fmt;
+
+use crate::group::Group;
+use crate::transcript::Transcript;
+
+// Domain separator for tying a login proof to the client's key share
+pub const KEY_SHARE_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/key-share/v1";
+
+// Domain separator for the transcript a session key is derived from
+pub const SESSION_KEY_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/session-key/v1";
+
+// HKDF info string for the session key
+const SESSION_KEY_INFO: &[u8] = b"pendersen session key";
+
+pub const SESSION_KEY_LENGTH: usize = 32;
+
+// Ephemeral Diffie-Hellman key pair in the protocol's group: secret a and g^a
+pub struct KeyShare<G: Group> {
+    secret: G::Scalar,
+    public: G::Element,
+}
+
+impl<G: Group> KeyShare<G> {
+    pub fn generate(group: &G) -> Self {
+        let secret = group.random_scalar();
+        KeyShare {
+            public: group.exp(&group.g(), &secret),
+            secret,
+        }
+    }
+
+    pub fn public(&self) -> &G::Element {
+        &self.public
+    }
+
+    // Shared secret peer^a, or None for a peer share that would make it the identity
+    pub fn agree(&self, group: &G, peer: &G::Element) -> Option<G::Element> {
+        let shared = group.exp(peer, &self.secret);
+        (*peer != group.identity() && shared != group.identity()).then_some(shared)
+    }
+}
+
+// Symmetric key both ends of a login derive when it carries a key exchange
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionKey(pub [u8; SESSION_KEY_LENGTH]);
+
+// Keep key material out of logs
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionKey(..)")
+    }
+}
+
+// Challenge a proof answers when the login carries the client's key share:
+// c' = H(parameters, c, client_share). The proof then authenticates the share,
+// so it cannot be swapped for one chosen by someone in the middle.
+pub fn bind_key_share<G: Group>(group: &G, c: &G::Scalar, client_share: &G::Element) -> G::Scalar {
+    let mut transcript = Transcript::new(KEY_SHARE_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"challenge", group.encode_scalar(c).as_bytes());
+    transcript.append_element(group, b"client_share", client_share);
+    transcript.challenge(group)
+}
+
+// HKDF-SHA512 over the Diffie-Hellman secret, salted with a hash of the login
+// transcript: the user, the commitment, the challenge answered and both shares
+pub fn session_key<G: Group>(
+    group: &G,
+    user: &str,
+    (r1, r2): (&G::Element, &G::Element),
+    c: &G::Scalar,
+    (client_share, server_share): (&G::Element, &G::Element),
+    shared: &G::Element,
+) -> SessionKey {
+    let mut transcript = Transcript::new(SESSION_KEY_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"user", user.as_bytes());
+    transcript.append_element(group, b"r1", r1);
+    transcript.append_element(group, b"r2", r2);
+    transcript.append(b"challenge", group.encode_scalar(c).as_bytes());
+    transcript.append_element(group, b"client_share", client_share);
+    transcript.append_element(group, b"server_share", server_share);
+    let hkdf = Hkdf::<Sha512>::new(Some(&transcript.digest()), group.encode_element(shared).as_bytes());
+    let mut key = [0; SESSION_KEY_LENGTH];
+    hkdf.expand(SESSION_KEY_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA512 output length");
+    SessionKey(key)
+}
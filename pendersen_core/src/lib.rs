@@ -0,0 +1,25 @@
+// src/lib.rs
+
+pub mod backend;
+pub mod generate;
+pub mod group;
+pub mod kdf;
+pub mod key_exchange;
+pub mod params;
+#[cfg(feature = "tls")]
+pub mod pem;
+pub mod primes;
+pub mod protocol;
+pub mod ristretto;
+pub mod server_key;
+pub mod standard;
+pub mod transcript;
+
+pub use backend::{Backend, ParameterSource};
+pub use group::{DecodeError, Group};
+pub use kdf::{KdfError, KdfParameters};
+pub use key_exchange::{KeyShare, SessionKey};
+pub use params::{GroupParameters, ParameterError, DEFAULT_PARAMETERS_PATH};
+pub use ristretto::RistrettoParameters;
+pub use server_key::{ServerKey, ServerProof};
+pub use transcript::Transcript;
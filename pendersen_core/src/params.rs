@@ -0,0 +1,216 @@
+use num_bigint::{BigInt, RandBigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use serde::Deserialize;
+use std::{error::Error, fmt, fs, path::Path, str::FromStr};
+
+use crate::group::{DecodeError, Group};
+use crate::primes::is_probable_prime;
+
+// Miller-Rabin rounds used when validating p and q on load
+const VALIDATION_ROUNDS: usize = 32;
+
+// Location of the shared parameter file relative to either binary's crate directory
+pub const DEFAULT_PARAMETERS_PATH: &str = "../parameters.json";
+
+// Raw parameter file layout; every value is a decimal string
+#[derive(Deserialize)]
+struct ParameterFile {
+    p: String,
+    q: String,
+    g: String,
+    h: String,
+}
+
+// Public group parameters shared by the client and the server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupParameters {
+    pub p: BigInt,
+    pub q: BigInt,
+    pub g: BigInt,
+    pub h: BigInt,
+}
+
+impl GroupParameters {
+    pub fn new(p: BigInt, q: BigInt, g: BigInt, h: BigInt) -> Self {
+        GroupParameters { p, q, g, h }
+    }
+
+    // Parse and validate parameters from the JSON representation used by `parameters.json`
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        let file: ParameterFile = serde_json::from_str(json)?;
+
+        let p = parse_value("p", &file.p)?;
+        let q = parse_value("q", &file.q)?;
+        let g = parse_value("g", &file.g)?;
+        let h = parse_value("h", &file.h)?;
+
+        let params = GroupParameters::new(p, q, g, h);
+        params.validate()?;
+        Ok(params)
+    }
+
+    // Check that p and q are prime, q divides p - 1, and g and h are distinct
+    // elements of order q
+    pub fn validate(&self) -> Result<(), ParameterError> {
+        if !is_probable_prime(&self.p, VALIDATION_ROUNDS) {
+            return Err(ParameterError::NotPrime("p"));
+        }
+        if !is_probable_prime(&self.q, VALIDATION_ROUNDS) {
+            return Err(ParameterError::NotPrime("q"));
+        }
+        if !(&self.p - 1u32).is_multiple_of(&self.q) {
+            return Err(ParameterError::OrderDoesNotDivide);
+        }
+        for (name, value) in [("g", &self.g), ("h", &self.h)] {
+            // q is prime, so any member other than 1 has order exactly q
+            if value.is_one() || !self.is_member(value) {
+                return Err(ParameterError::NotOfOrderQ(name));
+            }
+        }
+        if self.g == self.h {
+            return Err(ParameterError::EqualGenerators);
+        }
+        Ok(())
+    }
+
+    // Load parameters from a JSON file on disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let file_content = fs::read_to_string(path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        Self::from_json(&file_content)
+    }
+}
+
+// Reasons a parameter set is rejected by `GroupParameters::validate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterError {
+    NotPrime(&'static str),
+    OrderDoesNotDivide,
+    NotOfOrderQ(&'static str),
+    EqualGenerators,
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::NotPrime(name) => write!(f, "invalid parameters: {} is not prime", name),
+            ParameterError::OrderDoesNotDivide => write!(f, "invalid parameters: q does not divide p - 1"),
+            ParameterError::NotOfOrderQ(name) => {
+                write!(f, "invalid parameters: {} is not an element of order q in Z_p^*", name)
+            }
+            ParameterError::EqualGenerators => write!(f, "invalid parameters: g and h must differ"),
+        }
+    }
+}
+
+impl Error for ParameterError {}
+
+// The order-q subgroup of the multiplicative group mod p; elements and
+// scalars are plain integers written in decimal on the wire
+impl Group for GroupParameters {
+    type Element = BigInt;
+    type Scalar = BigInt;
+
+    fn name(&self) -> &'static str {
+        "modp"
+    }
+
+    fn transcript_parameters(&self) -> Vec<String> {
+        [&self.p, &self.q, &self.g, &self.h].iter().map(|v| v.to_string()).collect()
+    }
+
+    fn order(&self) -> BigInt {
+        self.q.clone()
+    }
+
+    fn g(&self) -> BigInt {
+        self.g.clone()
+    }
+
+    fn h(&self) -> BigInt {
+        self.h.clone()
+    }
+
+    fn identity(&self) -> BigInt {
+        BigInt::one()
+    }
+
+    fn is_member(&self, element: &BigInt) -> bool {
+        element.sign() == Sign::Plus && *element < self.p && element.modpow(&self.q, &self.p).is_one()
+    }
+
+    fn op(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        (a * b).mod_floor(&self.p)
+    }
+
+    fn exp(&self, base: &BigInt, exponent: &BigInt) -> BigInt {
+        base.modpow(exponent, &self.p)
+    }
+
+    fn scalar_from_bigint(&self, value: &BigInt) -> BigInt {
+        value.mod_floor(&self.q)
+    }
+
+    fn scalar_to_bigint(&self, scalar: &BigInt) -> BigInt {
+        scalar.clone()
+    }
+
+    fn scalar_add(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        (a + b).mod_floor(&self.q)
+    }
+
+    fn scalar_sub(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        (a - b).mod_floor(&self.q)
+    }
+
+    fn scalar_mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        (a * b).mod_floor(&self.q)
+    }
+
+    fn random_scalar(&self) -> BigInt {
+        rand::rngs::OsRng.gen_bigint_range(&BigInt::zero(), &self.q)
+    }
+
+    fn encode_element(&self, element: &BigInt) -> String {
+        element.to_string()
+    }
+
+    // Only values in the order-q subgroup are accepted: 0 < v < p and v^q == 1 mod p.
+    // Anything else could leak x mod a small factor of p - 1.
+    fn decode_element(&self, value: &str) -> Result<BigInt, DecodeError> {
+        let element = parse_integer(value)?;
+        if element.sign() != Sign::Plus || element >= self.p {
+            return Err(DecodeError(format!("{:?} is not in the range [1, p)", value)));
+        }
+        if !element.modpow(&self.q, &self.p).is_one() {
+            return Err(DecodeError(format!("{:?} is not in the order-q subgroup", value)));
+        }
+        Ok(element)
+    }
+
+    fn encode_scalar(&self, scalar: &BigInt) -> String {
+        scalar.to_string()
+    }
+
+    fn decode_scalar(&self, value: &str) -> Result<BigInt, DecodeError> {
+        let scalar = parse_integer(value)?;
+        // Ensure exponents are positive
+        if scalar.sign() == Sign::Minus {
+            return Err(DecodeError("Negative exponentiation is not allowed".to_string()));
+        }
+        if scalar >= self.q {
+            return Err(DecodeError(format!("{:?} is not in the range [0, q)", value)));
+        }
+        Ok(scalar)
+    }
+}
+
+fn parse_integer(value: &str) -> Result<BigInt, DecodeError> {
+    BigInt::from_str(value).map_err(|e| DecodeError(format!("{:?} is not an integer: {}", value, e)))
+}
+
+fn parse_value(name: &str, value: &str) -> Result<BigInt, Box<dyn Error>> {
+    BigInt::from_str(value).map_err(|e| format!("invalid {} value {:?}: {}", name, value, e).into())
+}
@@ -0,0 +1,70 @@
+use rustls::{internal::pemfile, Certificate, PrivateKey, RootCertStore};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Reasons a certificate or key file given to the server or client is refused
+#[derive(Debug)]
+pub enum PemError {
+    Read(PathBuf, io::Error),
+    NotPem(PathBuf),
+    Missing(PathBuf, &'static str),
+}
+
+impl fmt::Display for PemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PemError::Read(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            PemError::NotPem(path) => write!(f, "{} is not a PEM file", path.display()),
+            PemError::Missing(path, what) => write!(f, "{} holds no {}", path.display(), what),
+        }
+    }
+}
+
+impl Error for PemError {}
+
+impl From<PemError> for io::Error {
+    fn from(e: PemError) -> Self {
+        let kind = match &e {
+            PemError::Read(_, e) => e.kind(),
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+pub fn read(path: &Path) -> Result<Vec<u8>, PemError> {
+    let pem = fs::read(path).map_err(|e| PemError::Read(path.to_path_buf(), e))?;
+    if !pem.windows(11).any(|window| window == b"-----BEGIN ") {
+        return Err(PemError::NotPem(path.to_path_buf()));
+    }
+    Ok(pem)
+}
+
+pub fn certificates(path: &Path) -> Result<Vec<Certificate>, PemError> {
+    pemfile::certs(&mut read(path)?.as_slice())
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .ok_or_else(|| PemError::Missing(path.to_path_buf(), "certificates"))
+}
+
+// Trust the CA certificates in a PEM file on top of those already in `roots`
+pub fn add_roots(roots: &mut RootCertStore, path: &Path) -> Result<(), PemError> {
+    match roots.add_pem_file(&mut read(path)?.as_slice()) {
+        Ok((valid, _)) if valid > 0 => Ok(()),
+        _ => Err(PemError::Missing(path.to_path_buf(), "certificates")),
+    }
+}
+
+// The first PKCS#8 or, failing that, RSA private key in a PEM file
+pub fn private_key(path: &Path) -> Result<PrivateKey, PemError> {
+    let pem = read(path)?;
+    let keys = pemfile::pkcs8_private_keys(&mut pem.as_slice())
+        .ok()
+        .filter(|keys| !keys.is_empty())
+        .or_else(|| pemfile::rsa_private_keys(&mut pem.as_slice()).ok());
+    keys.and_then(|keys| keys.into_iter().next())
+        .ok_or_else(|| PemError::Missing(path.to_path_buf(), "private key"))
+}
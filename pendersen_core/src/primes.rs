@@ -0,0 +1,65 @@
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+// Miller-Rabin rounds used when none are specified; the error bound is 4^-64
+pub const DEFAULT_ROUNDS: usize = 64;
+
+// Odd primes below 2000, used to discard most composites before Miller-Rabin
+pub fn small_primes() -> Vec<u64> {
+    (3..2000u64)
+        .step_by(2)
+        .filter(|n| (3..).step_by(2).take_while(|d| d * d <= *n).all(|d| n % d != 0))
+        .collect()
+}
+
+// Whether n has a factor among `primes` other than itself
+pub fn has_small_factor(n: &BigInt, primes: &[u64]) -> bool {
+    primes.iter().any(|&p| {
+        let p = BigInt::from(p);
+        *n != p && n.is_multiple_of(&p)
+    })
+}
+
+// Probabilistic primality test with `rounds` random Miller-Rabin bases
+pub fn is_probable_prime(n: &BigInt, rounds: usize) -> bool {
+    let two = BigInt::from(2);
+    if *n < two {
+        return false;
+    }
+    if *n == two || *n == BigInt::from(3) {
+        return true;
+    }
+    if n.is_even() || has_small_factor(n, &small_primes()) {
+        return false;
+    }
+
+    // Write n - 1 = d * 2^r with d odd
+    let n_minus_one: BigInt = n - 1;
+    let mut d = n_minus_one.clone();
+    let mut r = 0u32;
+    while d.is_even() {
+        d >>= 1;
+        r += 1;
+    }
+
+    let mut rng = rand::thread_rng();
+    'witness: for _ in 0..rounds {
+        let a = rng.gen_bigint_range(&two, &n_minus_one);
+        let mut x = a.modpow(&d, n);
+        if x.is_one() || x == n_minus_one {
+            continue;
+        }
+        for _ in 1..r {
+            x = x.modpow(&two, n);
+            if x == n_minus_one {
+                continue 'witness;
+            }
+            if x.is_zero() || x.is_one() {
+                return false;
+            }
+        }
+        return false;
+    }
+    true
+}
@@ -0,0 +1,159 @@
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::Zero;
+
+use crate::group::Group;
+use crate::kdf::KdfParameters;
+use crate::transcript::Transcript;
+
+// Domain separator for the non-interactive login challenge
+pub const LOGIN_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/login/v1";
+
+// Domain separator for the proof of knowledge sent with a registration
+pub const REGISTRATION_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/register/v1";
+
+// Domain separator for the proof authorising a change of credentials
+pub const CHANGE_CREDENTIALS_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/change-credentials/v1";
+
+// Domain separator for tying a challenge to the connection that carries the answer
+pub const CHANNEL_BINDING_DOMAIN: &[u8] = b"pendersen-project/chaum-pedersen/channel-binding/v1";
+
+// TLS exporter label (RFC 5705, RFC 8446 section 7.5) for the channel binding value
+pub const CHANNEL_BINDING_EXPORTER_LABEL: &[u8] = b"EXPORTER-pendersen-channel-binding";
+
+// Length in bytes of the exported channel binding value
+pub const CHANNEL_BINDING_LENGTH: usize = 32;
+
+// Registration values y1 = g^x and y2 = h^x for the secret x
+pub fn public_key<G: Group>(group: &G, x: &G::Scalar) -> (G::Element, G::Element) {
+    (group.exp(&group.g(), x), group.exp(&group.h(), x))
+}
+
+// Commitment r1 = g^k and r2 = h^k for the nonce k
+pub fn commitment<G: Group>(group: &G, k: &G::Scalar) -> (G::Element, G::Element) {
+    public_key(group, k)
+}
+
+// Answer to the challenge c: s = (k - c * x) mod q
+pub fn response<G: Group>(group: &G, k: &G::Scalar, c: &G::Scalar, x: &G::Scalar) -> G::Scalar {
+    group.scalar_sub(k, &group.scalar_mul(c, x))
+}
+
+// Interactive challenge c, uniform in [0, 2^bits) when `bits` is set and
+// smaller than the group order, and uniform in [0, q) otherwise
+pub fn random_challenge<G: Group>(group: &G, bits: Option<u64>) -> G::Scalar {
+    match bits {
+        Some(bits) if bits < group.order().bits() => {
+            let bound = BigInt::from(1) << bits;
+            group.scalar_from_bigint(&rand::rngs::OsRng.gen_bigint_range(&BigInt::zero(), &bound))
+        }
+        _ => group.random_scalar(),
+    }
+}
+
+// Fiat-Shamir challenge for a login: c = H(parameters, user, y1, y2, r1, r2, context).
+// The context carries whatever makes the proof fresh, such as a timestamp.
+pub fn login_challenge<G: Group>(
+    group: &G,
+    user: &str,
+    (y1, y2): (&G::Element, &G::Element),
+    (r1, r2): (&G::Element, &G::Element),
+    context: &[u8],
+) -> G::Scalar {
+    let mut transcript = Transcript::new(LOGIN_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"user", user.as_bytes());
+    transcript.append_element(group, b"y1", y1);
+    transcript.append_element(group, b"y2", y2);
+    transcript.append_element(group, b"r1", r1);
+    transcript.append_element(group, b"r2", r2);
+    transcript.append(b"context", context);
+    transcript.challenge(group)
+}
+
+// Fiat-Shamir challenge for a registration: c = H(parameters, user, y1, y2, r1, r2, kdf).
+// A proof against it shows log_g y1 == log_h y2 and that the registrant knows that value.
+pub fn registration_challenge<G: Group>(
+    group: &G,
+    user: &str,
+    (y1, y2): (&G::Element, &G::Element),
+    (r1, r2): (&G::Element, &G::Element),
+    kdf: Option<&KdfParameters>,
+) -> G::Scalar {
+    let mut transcript = Transcript::new(REGISTRATION_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"user", user.as_bytes());
+    transcript.append_element(group, b"y1", y1);
+    transcript.append_element(group, b"y2", y2);
+    transcript.append_element(group, b"r1", r1);
+    transcript.append_element(group, b"r2", r2);
+    append_kdf(&mut transcript, kdf);
+    transcript.challenge(group)
+}
+
+// Fiat-Shamir challenge for replacing (y1, y2) with (new_y1, new_y2): the proof
+// with the old secret also commits to the new key and its KDF parameters, so
+// neither can be swapped out in transit
+pub fn change_credentials_challenge<G: Group>(
+    group: &G,
+    user: &str,
+    (y1, y2): (&G::Element, &G::Element),
+    (r1, r2): (&G::Element, &G::Element),
+    (new_y1, new_y2): (&G::Element, &G::Element),
+    kdf: Option<&KdfParameters>,
+    context: &[u8],
+) -> G::Scalar {
+    let mut transcript = Transcript::new(CHANGE_CREDENTIALS_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"user", user.as_bytes());
+    transcript.append_element(group, b"y1", y1);
+    transcript.append_element(group, b"y2", y2);
+    transcript.append_element(group, b"r1", r1);
+    transcript.append_element(group, b"r2", r2);
+    transcript.append_element(group, b"new_y1", new_y1);
+    transcript.append_element(group, b"new_y2", new_y2);
+    append_kdf(&mut transcript, kdf);
+    transcript.append(b"context", context);
+    transcript.challenge(group)
+}
+
+// Challenge actually answered on a channel with exporter value `binding`:
+// c' = H(parameters, c, binding). Prover and verifier each use the value of
+// their own end of the connection, so an answer relayed from another TLS
+// session was computed against a different c' and fails verification.
+pub fn bind_challenge<G: Group>(group: &G, c: &G::Scalar, binding: &[u8]) -> G::Scalar {
+    let mut transcript = Transcript::new(CHANNEL_BINDING_DOMAIN);
+    transcript.append_group(group);
+    transcript.append(b"challenge", group.encode_scalar(c).as_bytes());
+    transcript.append(b"binding", binding);
+    transcript.challenge(group)
+}
+
+// Check that g^s * y1^c == r1 and h^s * y2^c == r2
+pub fn verify<G: Group>(
+    group: &G,
+    (y1, y2): (&G::Element, &G::Element),
+    (r1, r2): (&G::Element, &G::Element),
+    c: &G::Scalar,
+    s: &G::Scalar,
+) -> bool {
+    let r1p = combine(group, &group.g(), s, y1, c);
+    let r2p = combine(group, &group.h(), s, y2, c);
+    r1p == *r1 && r2p == *r2
+}
+
+fn append_kdf(transcript: &mut Transcript, kdf: Option<&KdfParameters>) {
+    match kdf {
+        Some(kdf) => {
+            transcript.append(b"kdf_salt", &kdf.salt);
+            transcript.append(b"kdf_memory_kib", &kdf.memory_kib.to_be_bytes());
+            transcript.append(b"kdf_iterations", &kdf.iterations.to_be_bytes());
+            transcript.append(b"kdf_parallelism", &kdf.parallelism.to_be_bytes());
+        }
+        None => transcript.append(b"kdf", b""),
+    }
+}
+
+// Compute base^s * y^c
+fn combine<G: Group>(group: &G, base: &G::Element, s: &G::Scalar, y: &G::Element, c: &G::Scalar) -> G::Element {
+    group.op(&group.exp(base, s), &group.exp(y, c))
+}
@@ -0,0 +1,132 @@
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use sha2::Sha512;
+
+use crate::group::{DecodeError, Group};
+
+// Domain separation string used to derive the second generator h
+pub const H_GENERATOR_DOMAIN: &[u8] = b"pendersen-project ristretto255 h generator";
+
+// Generators of the Ristretto255 group; g is the standard basepoint and h is
+// derived by hashing to the group so that nobody knows log_g h
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RistrettoParameters {
+    pub g: RistrettoPoint,
+    pub h: RistrettoPoint,
+}
+
+impl Default for RistrettoParameters {
+    fn default() -> Self {
+        RistrettoParameters {
+            g: RISTRETTO_BASEPOINT_POINT,
+            h: RistrettoPoint::hash_from_bytes::<Sha512>(H_GENERATOR_DOMAIN),
+        }
+    }
+}
+
+// Order l = 2^252 + 27742317777372353535851937790883648493 of the group
+pub fn group_order() -> BigInt {
+    let tail: BigInt = "27742317777372353535851937790883648493".parse().unwrap();
+    (BigInt::from(1) << 252) + tail
+}
+
+// Points travel as the hex encoding of their 32-byte compressed form and
+// scalars as the hex encoding of their canonical 32-byte little-endian form
+impl Group for RistrettoParameters {
+    type Element = RistrettoPoint;
+    type Scalar = Scalar;
+
+    fn name(&self) -> &'static str {
+        "ristretto255"
+    }
+
+    fn transcript_parameters(&self) -> Vec<String> {
+        vec![self.encode_element(&self.g), self.encode_element(&self.h)]
+    }
+
+    fn order(&self) -> BigInt {
+        group_order()
+    }
+
+    fn g(&self) -> RistrettoPoint {
+        self.g
+    }
+
+    fn h(&self) -> RistrettoPoint {
+        self.h
+    }
+
+    fn identity(&self) -> RistrettoPoint {
+        RistrettoPoint::identity()
+    }
+
+    // Every decodable Ristretto point is in the prime-order group
+    fn is_member(&self, _element: &RistrettoPoint) -> bool {
+        true
+    }
+
+    fn op(&self, a: &RistrettoPoint, b: &RistrettoPoint) -> RistrettoPoint {
+        a + b
+    }
+
+    fn exp(&self, base: &RistrettoPoint, exponent: &Scalar) -> RistrettoPoint {
+        base * exponent
+    }
+
+    fn scalar_from_bigint(&self, value: &BigInt) -> Scalar {
+        let (_, bytes) = value.mod_floor(&group_order()).to_bytes_le();
+        let mut buf = [0u8; 32];
+        buf[..bytes.len()].copy_from_slice(&bytes);
+        Scalar::from_bytes_mod_order(buf)
+    }
+
+    fn scalar_to_bigint(&self, scalar: &Scalar) -> BigInt {
+        BigInt::from_bytes_le(Sign::Plus, scalar.as_bytes())
+    }
+
+    fn scalar_add(&self, a: &Scalar, b: &Scalar) -> Scalar {
+        a + b
+    }
+
+    fn scalar_sub(&self, a: &Scalar, b: &Scalar) -> Scalar {
+        a - b
+    }
+
+    fn scalar_mul(&self, a: &Scalar, b: &Scalar) -> Scalar {
+        a * b
+    }
+
+    fn random_scalar(&self) -> Scalar {
+        Scalar::random(&mut rand::rngs::OsRng)
+    }
+
+    fn encode_element(&self, element: &RistrettoPoint) -> String {
+        hex::encode(element.compress().as_bytes())
+    }
+
+    fn decode_element(&self, value: &str) -> Result<RistrettoPoint, DecodeError> {
+        CompressedRistretto(decode_32(value)?)
+            .decompress()
+            .ok_or_else(|| DecodeError(format!("{:?} is not a valid ristretto255 point", value)))
+    }
+
+    fn encode_scalar(&self, scalar: &Scalar) -> String {
+        hex::encode(scalar.as_bytes())
+    }
+
+    fn decode_scalar(&self, value: &str) -> Result<Scalar, DecodeError> {
+        Option::from(Scalar::from_canonical_bytes(decode_32(value)?))
+            .ok_or_else(|| DecodeError(format!("{:?} is not a canonical ristretto255 scalar", value)))
+    }
+}
+
+fn decode_32(value: &str) -> Result<[u8; 32], DecodeError> {
+    let bytes = hex::decode(value).map_err(|e| DecodeError(format!("{:?} is not hex: {}", value, e)))?;
+    bytes
+        .try_into()
+        .map_err(|_| DecodeError(format!("{:?} is not 32 bytes long", value)))
+}
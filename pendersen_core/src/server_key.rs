@@ -0,0 +1,99 @@
+use crate::group::Group;
+use crate::transcript::Transcript;
+
+// Domain separator for deriving a server key from its seed
+pub const SERVER_KEY_DOMAIN: &[u8] = b"pendersen-project/schnorr/server-key/v1";
+
+// Domain separator for the proof a server signs a finished login with
+pub const SERVER_PROOF_DOMAIN: &[u8] = b"pendersen-project/schnorr/server-proof/v1";
+
+// Long-term key the server proves its identity with: secret z and Z = g^z.
+// Clients pin Z and refuse logins the server cannot prove with z.
+pub struct ServerKey<G: Group> {
+    secret: G::Scalar,
+    public: G::Element,
+}
+
+impl<G: Group> ServerKey<G> {
+    pub fn generate(group: &G) -> Self {
+        Self::from_secret(group, group.random_scalar())
+    }
+
+    // The same seed gives the same key in a given group, so a key file can hold
+    // random bytes that work whichever group the server is started with
+    pub fn from_seed(group: &G, seed: &[u8]) -> Self {
+        let mut transcript = Transcript::new(SERVER_KEY_DOMAIN);
+        transcript.append_group(group);
+        transcript.append(b"seed", seed);
+        Self::from_secret(group, transcript.challenge(group))
+    }
+
+    fn from_secret(group: &G, secret: G::Scalar) -> Self {
+        ServerKey {
+            public: group.exp(&group.g(), &secret),
+            secret,
+        }
+    }
+
+    pub fn public(&self) -> &G::Element {
+        &self.public
+    }
+
+    // Schnorr proof of knowledge of z over the login: t random, r = g^t,
+    // e = H(parameters, Z, r, login) and s = t - e * z
+    pub fn prove(&self, group: &G, login: &LoginRecord<'_, G>) -> ServerProof<G> {
+        let t = group.random_scalar();
+        let r = group.exp(&group.g(), &t);
+        let e = server_challenge(group, &self.public, &r, login);
+        ServerProof {
+            s: group.scalar_sub(&t, &group.scalar_mul(&e, &self.secret)),
+            r,
+        }
+    }
+}
+
+// A finished login as both ends saw it. The server's proof covers all of it, so
+// it vouches for the challenge answered (and with it the TLS session), the key
+// exchange and the session token together.
+pub struct LoginRecord<'a, G: Group> {
+    pub user: &'a str,
+    pub commitment: (&'a G::Element, &'a G::Element),
+    // The challenge the client's proof answered, after any binding
+    pub challenge: &'a G::Scalar,
+    pub response: &'a G::Scalar,
+    // Client and server shares, when the login carried a key exchange
+    pub key_shares: Option<(&'a G::Element, &'a G::Element)>,
+    pub session_id: &'a str,
+    pub expires_at: u64,
+}
+
+pub struct ServerProof<G: Group> {
+    pub r: G::Element,
+    pub s: G::Scalar,
+}
+
+fn server_challenge<G: Group>(group: &G, public: &G::Element, r: &G::Element, login: &LoginRecord<'_, G>) -> G::Scalar {
+    let (r1, r2) = login.commitment;
+    let mut transcript = Transcript::new(SERVER_PROOF_DOMAIN);
+    transcript.append_group(group);
+    transcript.append_element(group, b"server_key", public);
+    transcript.append_element(group, b"r", r);
+    transcript.append(b"user", login.user.as_bytes());
+    transcript.append_element(group, b"r1", r1);
+    transcript.append_element(group, b"r2", r2);
+    transcript.append(b"challenge", group.encode_scalar(login.challenge).as_bytes());
+    transcript.append(b"response", group.encode_scalar(login.response).as_bytes());
+    if let Some((client_share, server_share)) = login.key_shares {
+        transcript.append_element(group, b"client_share", client_share);
+        transcript.append_element(group, b"server_share", server_share);
+    }
+    transcript.append(b"session_id", login.session_id.as_bytes());
+    transcript.append(b"expires_at", &login.expires_at.to_be_bytes());
+    transcript.challenge(group)
+}
+
+// Check g^s * Z^e == r for the pinned server key Z
+pub fn verify<G: Group>(group: &G, public: &G::Element, login: &LoginRecord<'_, G>, proof: &ServerProof<G>) -> bool {
+    let e = server_challenge(group, public, &proof.r, login);
+    group.op(&group.exp(&group.g(), &proof.s), &group.exp(public, &e)) == proof.r
+}
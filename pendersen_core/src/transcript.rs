@@ -0,0 +1,49 @@
+use num_bigint::{BigInt, Sign};
+use sha2::{Digest, Sha512};
+
+use crate::group::Group;
+
+// Running hash over a labelled sequence of values, used to derive challenges
+// non-interactively. Every value is length-prefixed so that distinct
+// transcripts can never hash the same byte string.
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: Sha512,
+}
+
+impl Transcript {
+    pub fn new(domain: &[u8]) -> Self {
+        let mut transcript = Transcript { hasher: Sha512::new() };
+        transcript.append(b"domain", domain);
+        transcript
+    }
+
+    pub fn append(&mut self, label: &[u8], value: &[u8]) {
+        for part in [label, value] {
+            self.hasher.update((part.len() as u64).to_be_bytes());
+            self.hasher.update(part);
+        }
+    }
+
+    // Bind the group name and the public values identifying its instance
+    pub fn append_group<G: Group>(&mut self, group: &G) {
+        self.append(b"group", group.name().as_bytes());
+        for value in group.transcript_parameters() {
+            self.append(b"parameter", value.as_bytes());
+        }
+    }
+
+    pub fn append_element<G: Group>(&mut self, group: &G, label: &[u8], element: &G::Element) {
+        self.append(label, group.encode_element(element).as_bytes());
+    }
+
+    // Finish the transcript as a scalar: the 512-bit digest reduced mod the group order
+    pub fn challenge<G: Group>(self, group: &G) -> G::Scalar {
+        group.scalar_from_bigint(&BigInt::from_bytes_be(Sign::Plus, &self.digest()))
+    }
+
+    // Finish the transcript as its raw 512-bit digest
+    pub fn digest(self) -> [u8; 64] {
+        self.hasher.finalize().into()
+    }
+}
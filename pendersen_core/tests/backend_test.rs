@@ -0,0 +1,66 @@
+use pendersen_core::protocol::{commitment, public_key, response, verify};
+use pendersen_core::{Backend, Group, RistrettoParameters};
+use num_bigint::BigInt;
+
+fn prove<G: Group>(group: &G, x: &BigInt, password: &BigInt) -> bool {
+    let (y1, y2) = public_key(group, &group.scalar_from_bigint(x));
+    let k = group.random_scalar();
+    let (r1, r2) = commitment(group, &k);
+    let c = group.random_scalar();
+    let s = response(group, &k, &c, &group.scalar_from_bigint(password));
+    verify(group, (&y1, &y2), (&r1, &r2), &c, &s)
+}
+
+#[test]
+fn test_ristretto_honest_proof_verifies() {
+    match Backend::load("../parameters-ristretto255.json").unwrap() {
+        Backend::Ristretto255(group) => assert!(prove(&group, &BigInt::from(123456), &BigInt::from(123456))),
+        other => panic!("expected ristretto255, got {}", other.name()),
+    }
+}
+
+#[test]
+fn test_ristretto_wrong_secret_is_rejected() {
+    let group = RistrettoParameters::default();
+    assert!(!prove(&group, &BigInt::from(123456), &BigInt::from(654321)));
+}
+
+#[test]
+fn test_modp_backend_is_the_default() {
+    match Backend::load("../parameters.json").unwrap() {
+        Backend::ModP(group) => assert!(prove(&group, &BigInt::from(42), &BigInt::from(42))),
+        other => panic!("expected modp, got {}", other.name()),
+    }
+}
+
+#[test]
+fn test_ristretto_elements_are_32_bytes() {
+    let group = RistrettoParameters::default();
+    let (y1, _) = public_key(&group, &group.scalar_from_bigint(&BigInt::from(7)));
+    let encoded = group.encode_element(&y1);
+    assert_eq!(encoded.len(), 64);
+    assert_eq!(group.decode_element(&encoded).unwrap(), y1);
+}
+
+#[test]
+fn test_ristretto_rejects_invalid_encodings() {
+    let group = RistrettoParameters::default();
+    assert!(group.decode_element(&"ff".repeat(32)).is_err());
+    assert!(group.decode_element("abcd").is_err());
+    assert!(group.decode_scalar(&"ff".repeat(32)).is_err());
+}
+
+#[test]
+fn test_scalar_round_trip_through_bigint() {
+    let group = RistrettoParameters::default();
+    let value = group.order() - 1;
+    let scalar = group.scalar_from_bigint(&value);
+    assert_eq!(group.scalar_to_bigint(&scalar), value);
+    assert_eq!(group.scalar_add(&scalar, &group.scalar_from_bigint(&BigInt::from(1))), group.scalar_from_bigint(&BigInt::from(0)));
+}
+
+#[test]
+fn test_unknown_group_is_rejected() {
+    let err = Backend::from_json(r#"{"group": "secp256k1"}"#).unwrap_err();
+    assert!(err.to_string().contains("unknown group"));
+}
@@ -0,0 +1,60 @@
+use pendersen_core::generate::{audit, generate};
+use pendersen_core::primes::is_probable_prime;
+use pendersen_core::protocol::{commitment, public_key, response, verify};
+use pendersen_core::Group;
+use num_bigint::BigInt;
+
+#[test]
+fn test_primality() {
+    assert!(is_probable_prime(&BigInt::from(2), 16));
+    assert!(is_probable_prime(&BigInt::from(1_000_000_007), 16));
+    assert!(!is_probable_prime(&BigInt::from(1), 16));
+    // Carmichael number 561 = 3 * 11 * 17 and a product of two large primes
+    assert!(!is_probable_prime(&BigInt::from(561), 16));
+    assert!(!is_probable_prime(&(BigInt::from(1_000_000_007) * BigInt::from(998_244_353)), 16));
+    let p: BigInt = "35535027358615730459".parse().unwrap();
+    assert!(is_probable_prime(&p, 16));
+}
+
+#[test]
+fn test_generated_group_is_a_safe_prime_group() {
+    let file = generate(128, b"test seed").unwrap();
+    let params = file.parameters().unwrap();
+
+    assert_eq!(params.p.bits(), 128);
+    assert_eq!(params.p, 2 * &params.q + 1);
+    assert!(is_probable_prime(&params.p, 32));
+    assert!(is_probable_prime(&params.q, 32));
+    assert!(params.is_member(&params.g) && params.g != BigInt::from(1));
+    assert!(params.is_member(&params.h) && params.h != BigInt::from(1));
+    assert_ne!(params.g, params.h);
+
+    let x = BigInt::from(1234);
+    let k = params.random_scalar();
+    let c = params.random_scalar();
+    let (y1, y2) = public_key(&params, &x);
+    let (r1, r2) = commitment(&params, &k);
+    assert!(verify(&params, (&y1, &y2), (&r1, &r2), &c, &response(&params, &k, &c, &x)));
+}
+
+#[test]
+fn test_generation_is_reproducible_from_the_seed() {
+    let first = generate(96, b"audit me").unwrap();
+    let second = generate(96, b"audit me").unwrap();
+    assert_eq!(first.p, second.p);
+    assert_eq!(first.h, second.h);
+    assert!(audit(&first).is_ok());
+
+    let other = generate(96, b"another seed").unwrap();
+    assert_ne!(first.p, other.p);
+}
+
+#[test]
+fn test_audit_detects_tampering() {
+    let mut file = generate(96, b"audit me").unwrap();
+    // Still an element of order q, but one whose discrete log base g is known
+    let params = file.parameters().unwrap();
+    file.h = params.g.modpow(&BigInt::from(2), &params.p).to_string();
+    let err = audit(&file).unwrap_err();
+    assert!(err.to_string().contains("h does not match"));
+}
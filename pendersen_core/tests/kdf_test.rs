@@ -0,0 +1,53 @@
+use pendersen_core::{Group, GroupParameters, KdfParameters, RistrettoParameters, DEFAULT_PARAMETERS_PATH};
+
+// Cheap settings so the tests do not spend their time in Argon2
+fn test_kdf(salt: &[u8]) -> KdfParameters {
+    KdfParameters {
+        salt: salt.to_vec(),
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    }
+}
+
+#[test]
+fn test_derivation_is_deterministic() {
+    let group = RistrettoParameters::default();
+    let kdf = test_kdf(b"0123456789abcdef");
+    let first = kdf.derive_secret(&group, "correct horse battery staple").unwrap();
+    let second = kdf.derive_secret(&group, "correct horse battery staple").unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_derivation_depends_on_password_and_salt() {
+    let group = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let kdf = test_kdf(b"0123456789abcdef");
+    let x = kdf.derive_secret(&group, "pässwörd").unwrap();
+    assert_ne!(x, kdf.derive_secret(&group, "password").unwrap());
+    assert_ne!(x, test_kdf(b"fedcba9876543210").derive_secret(&group, "pässwörd").unwrap());
+    assert!(x < group.order());
+}
+
+#[test]
+fn test_generated_parameters_use_fresh_salts() {
+    let first = KdfParameters::generate();
+    let second = KdfParameters::generate();
+    assert!(first.validate().is_ok());
+    assert_eq!(first.salt.len(), 16);
+    assert_ne!(first.salt, second.salt);
+}
+
+#[test]
+fn test_out_of_bounds_parameters_are_rejected() {
+    let group = RistrettoParameters::default();
+    assert!(test_kdf(b"short").derive_secret(&group, "pw").is_err());
+
+    let mut kdf = test_kdf(b"0123456789abcdef");
+    kdf.memory_kib = 1 << 30;
+    assert!(kdf.validate().unwrap_err().to_string().contains("memory"));
+
+    let mut kdf = test_kdf(b"0123456789abcdef");
+    kdf.iterations = 0;
+    assert!(kdf.validate().is_err());
+}
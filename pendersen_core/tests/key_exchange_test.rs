@@ -0,0 +1,81 @@
+use pendersen_core::key_exchange::{bind_key_share, session_key};
+use pendersen_core::protocol::{commitment, public_key, random_challenge, response, verify};
+use pendersen_core::{Group, GroupParameters, KeyShare, RistrettoParameters, SessionKey, DEFAULT_PARAMETERS_PATH};
+
+// Run an interactive login with a key exchange and return the keys each side derives
+fn exchange<G: Group>(group: &G) -> (SessionKey, SessionKey) {
+    let x = group.random_scalar();
+    let k = group.random_scalar();
+    let (y1, y2) = public_key(group, &x);
+    let (r1, r2) = commitment(group, &k);
+    let client = KeyShare::generate(group);
+    let c = bind_key_share(group, &random_challenge(group, None), client.public());
+    let s = response(group, &k, &c, &x);
+    assert!(verify(group, (&y1, &y2), (&r1, &r2), &c, &s));
+
+    let server = KeyShare::generate(group);
+    let shares = (client.public(), server.public());
+    let server_key = session_key(group, "alice", (&r1, &r2), &c, shares, &server.agree(group, client.public()).unwrap());
+    let client_key = session_key(group, "alice", (&r1, &r2), &c, shares, &client.agree(group, server.public()).unwrap());
+    (client_key, server_key)
+}
+
+#[test]
+fn test_both_sides_derive_the_same_key() {
+    let (client, server) = exchange(&RistrettoParameters::default());
+    assert_eq!(client, server);
+
+    let (client, server) = exchange(&GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap());
+    assert_eq!(client, server);
+}
+
+#[test]
+fn test_each_login_derives_a_fresh_key() {
+    let group = RistrettoParameters::default();
+
+    assert_ne!(exchange(&group).0, exchange(&group).0);
+}
+
+#[test]
+fn test_key_is_bound_to_the_transcript() {
+    let group = RistrettoParameters::default();
+    let (r1, r2) = commitment(&group, &group.random_scalar());
+    let c = group.random_scalar();
+    let client = KeyShare::generate(&group);
+    let server = KeyShare::generate(&group);
+    let shared = server.agree(&group, client.public()).unwrap();
+    let shares = (client.public(), server.public());
+
+    let key = session_key(&group, "alice", (&r1, &r2), &c, shares, &shared);
+
+    assert_ne!(key, session_key(&group, "bob", (&r1, &r2), &c, shares, &shared));
+    assert_ne!(key, session_key(&group, "alice", (&r2, &r1), &c, shares, &shared));
+    assert_ne!(key, session_key(&group, "alice", (&r1, &r2), &group.random_scalar(), shares, &shared));
+    assert_ne!(key, session_key(&group, "alice", (&r1, &r2), &c, (server.public(), client.public()), &shared));
+}
+
+#[test]
+fn test_swapped_client_share_changes_the_challenge() {
+    let group = RistrettoParameters::default();
+    let c = group.random_scalar();
+    let honest = KeyShare::generate(&group);
+    let swapped = KeyShare::generate(&group);
+
+    assert_ne!(bind_key_share(&group, &c, honest.public()), bind_key_share(&group, &c, swapped.public()));
+    assert_ne!(bind_key_share(&group, &c, honest.public()), c);
+}
+
+#[test]
+fn test_identity_share_is_refused() {
+    let group = RistrettoParameters::default();
+    let share = KeyShare::generate(&group);
+
+    assert!(share.agree(&group, &group.identity()).is_none());
+}
+
+#[test]
+fn test_session_key_is_not_printed() {
+    let group = RistrettoParameters::default();
+
+    assert_eq!(format!("{:?}", exchange(&group).0), "SessionKey(..)");
+}
@@ -0,0 +1,241 @@
+use pendersen_core::protocol::{bind_challenge, change_credentials_challenge, commitment, login_challenge, public_key, random_challenge, registration_challenge, response, verify};
+use pendersen_core::{Group, GroupParameters, KdfParameters, DEFAULT_PARAMETERS_PATH};
+use num_bigint::BigInt;
+
+fn small_group() -> GroupParameters {
+    // p = 23 = 2 * 11 + 1, g and h generate the subgroup of order 11
+    GroupParameters::new(BigInt::from(23), BigInt::from(11), BigInt::from(4), BigInt::from(9))
+}
+
+#[test]
+fn test_honest_proof_verifies() {
+    let params = small_group();
+    let x = BigInt::from(6);
+    let k = BigInt::from(7);
+    let c = BigInt::from(4);
+
+    let (y1, y2) = public_key(&params, &x);
+    let (r1, r2) = commitment(&params, &k);
+    let s = response(&params, &k, &c, &x);
+
+    assert!(verify(&params, (&y1, &y2), (&r1, &r2), &c, &s));
+}
+
+#[test]
+fn test_wrong_secret_is_rejected() {
+    let params = small_group();
+    let x = BigInt::from(6);
+    let k = BigInt::from(7);
+    let c = BigInt::from(4);
+
+    let (y1, y2) = public_key(&params, &x);
+    let (r1, r2) = commitment(&params, &k);
+    let s = response(&params, &k, &c, &BigInt::from(5));
+
+    assert!(!verify(&params, (&y1, &y2), (&r1, &r2), &c, &s));
+}
+
+#[test]
+fn test_load_shared_parameter_file() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    assert_eq!(params.p, "35535027358615730459".parse::<BigInt>().unwrap());
+    assert_eq!(params.h, "18307430318265890875".parse::<BigInt>().unwrap());
+}
+
+#[test]
+fn test_invalid_value_is_reported() {
+    let err = GroupParameters::from_json(r#"{"p": "23", "q": "eleven", "g": "4", "h": "9"}"#).unwrap_err();
+    assert!(err.to_string().contains("invalid q value"));
+}
+
+#[test]
+fn test_modp_membership() {
+    let params = small_group();
+    assert!(params.is_member(&params.g()));
+    assert!(params.is_member(&params.identity()));
+    // 5 generates the full group of order 22, not the subgroup of order 11
+    assert!(!params.is_member(&BigInt::from(5)));
+    assert!(!params.is_member(&BigInt::from(0)));
+    assert!(!params.is_member(&BigInt::from(23)));
+}
+
+#[test]
+fn test_negative_scalar_is_rejected() {
+    let params = small_group();
+    assert!(params.decode_scalar("-3").is_err());
+    assert_eq!(params.decode_scalar("3").unwrap(), BigInt::from(3));
+}
+
+#[test]
+fn test_decode_element_rejects_values_outside_the_subgroup() {
+    let params = small_group();
+    assert_eq!(params.decode_element("4").unwrap(), BigInt::from(4));
+    assert_eq!(params.decode_element("1").unwrap(), BigInt::from(1));
+    // Out of range: zero, negative, p and above
+    for value in ["0", "-4", "23", "27"] {
+        assert!(params.decode_element(value).unwrap_err().to_string().contains("[1, p)"), "{}", value);
+    }
+    // p - 1 has order 2 and 5 has order 22; both would leak x mod 2
+    for value in ["22", "5"] {
+        assert!(params.decode_element(value).unwrap_err().to_string().contains("subgroup"), "{}", value);
+    }
+}
+
+#[test]
+fn test_decode_element_rejects_order_two_element_of_real_group() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let p_minus_one = (&params.p - BigInt::from(1)).to_string();
+    assert!(params.decode_element(&p_minus_one).is_err());
+    assert!(params.decode_element(&params.p.to_string()).is_err());
+}
+
+#[test]
+fn test_decode_scalar_rejects_values_outside_zero_to_q() {
+    let params = small_group();
+    assert_eq!(params.decode_scalar("0").unwrap(), BigInt::from(0));
+    assert_eq!(params.decode_scalar("10").unwrap(), BigInt::from(10));
+    assert!(params.decode_scalar("11").is_err());
+    assert!(params.decode_scalar("1000000000000000000000000").is_err());
+}
+
+#[test]
+fn test_fiat_shamir_proof_verifies() {
+    let params = small_group();
+    let x = BigInt::from(6);
+    let k = BigInt::from(7);
+    let (y1, y2) = public_key(&params, &x);
+    let (r1, r2) = commitment(&params, &k);
+
+    let c = login_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), b"1700000000");
+    let s = response(&params, &k, &c, &x);
+
+    let recomputed = login_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), b"1700000000");
+    assert!(verify(&params, (&y1, &y2), (&r1, &r2), &recomputed, &s));
+}
+
+#[test]
+fn test_fiat_shamir_challenge_binds_user_and_context() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let (y1, y2) = public_key(&params, &BigInt::from(6));
+    let (r1, r2) = commitment(&params, &BigInt::from(7));
+
+    let c = login_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), b"1");
+    assert_ne!(c, login_challenge(&params, "bob", (&y1, &y2), (&r1, &r2), b"1"));
+    assert_ne!(c, login_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), b"2"));
+    assert_ne!(c, login_challenge(&params, "alice", (&y2, &y1), (&r1, &r2), b"1"));
+}
+
+#[test]
+fn test_change_credentials_challenge_binds_new_key_and_kdf() {
+    let params = small_group();
+    let (y1, y2) = public_key(&params, &BigInt::from(6));
+    let (r1, r2) = commitment(&params, &BigInt::from(7));
+    let (n1, n2) = public_key(&params, &BigInt::from(3));
+    let kdf = KdfParameters { salt: b"0123456789abcdef".to_vec(), memory_kib: 64, iterations: 1, parallelism: 1 };
+
+    let c = change_credentials_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), (&n1, &n2), Some(&kdf), b"1");
+    assert_ne!(c, change_credentials_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), (&y1, &y2), Some(&kdf), b"1"));
+    assert_ne!(c, change_credentials_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), (&n1, &n2), None, b"1"));
+    assert_ne!(c, login_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), b"1"));
+}
+
+#[test]
+fn test_answer_bound_to_one_channel_fails_on_another() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let x = BigInt::from(6);
+    let k = BigInt::from(7);
+    let (y1, y2) = public_key(&params, &x);
+    let (r1, r2) = commitment(&params, &k);
+    let c = BigInt::from(4);
+
+    // The victim answers on its own connection
+    let s = response(&params, &k, &bind_challenge(&params, &c, &[1; 32]), &x);
+
+    assert!(verify(&params, (&y1, &y2), (&r1, &r2), &bind_challenge(&params, &c, &[1; 32]), &s));
+    // A relay forwarding s is verified against its own connection's exporter value
+    assert!(!verify(&params, (&y1, &y2), (&r1, &r2), &bind_challenge(&params, &c, &[2; 32]), &s));
+    assert!(!verify(&params, (&y1, &y2), (&r1, &r2), &c, &s));
+}
+
+#[test]
+fn test_registration_proof_requires_equal_logs() {
+    let params = small_group();
+    let k = BigInt::from(7);
+    let (r1, r2) = commitment(&params, &k);
+
+    // Honest registrant: y1 = g^6, y2 = h^6
+    let (y1, y2) = public_key(&params, &BigInt::from(6));
+    let c = registration_challenge(&params, "alice", (&y1, &y2), (&r1, &r2), None);
+    let s = response(&params, &k, &c, &BigInt::from(6));
+    assert!(verify(&params, (&y1, &y2), (&r1, &r2), &c, &s));
+
+    // y2 = h^5 cannot be proven with either exponent
+    let (_, bad_y2) = public_key(&params, &BigInt::from(5));
+    let c = registration_challenge(&params, "alice", (&y1, &bad_y2), (&r1, &r2), None);
+    for x in [6, 5] {
+        let s = response(&params, &k, &c, &BigInt::from(x));
+        assert!(!verify(&params, (&y1, &bad_y2), (&r1, &r2), &c, &s));
+    }
+}
+
+fn params_json(p: &str, q: &str, g: &str, h: &str) -> String {
+    format!(r#"{{"p": "{}", "q": "{}", "g": "{}", "h": "{}"}}"#, p, q, g, h)
+}
+
+fn load_error(p: &str, q: &str, g: &str, h: &str) -> String {
+    GroupParameters::from_json(&params_json(p, q, g, h)).unwrap_err().to_string()
+}
+
+#[test]
+fn test_valid_parameters_are_accepted() {
+    assert!(GroupParameters::from_json(&params_json("23", "11", "4", "9")).is_ok());
+    assert!(small_group().validate().is_ok());
+}
+
+#[test]
+fn test_typo_in_p_is_rejected() {
+    // The shipped p with its last digit changed
+    let err = load_error("35535027358615730458", "17767513679307865229", "6025122419218774389", "18307430318265890875");
+    assert_eq!(err, "invalid parameters: p is not prime");
+}
+
+#[test]
+fn test_composite_q_is_rejected() {
+    assert_eq!(load_error("23", "22", "4", "9"), "invalid parameters: q is not prime");
+}
+
+#[test]
+fn test_q_must_divide_p_minus_one() {
+    assert_eq!(load_error("23", "7", "4", "9"), "invalid parameters: q does not divide p - 1");
+}
+
+#[test]
+fn test_generators_must_have_order_q() {
+    // 5 generates all of Z_23^*, 1 is the identity and 23 is out of range
+    assert_eq!(load_error("23", "11", "5", "9"), "invalid parameters: g is not an element of order q in Z_p^*");
+    assert_eq!(load_error("23", "11", "4", "1"), "invalid parameters: h is not an element of order q in Z_p^*");
+    assert_eq!(load_error("23", "11", "4", "23"), "invalid parameters: h is not an element of order q in Z_p^*");
+}
+
+#[test]
+fn test_generators_must_differ() {
+    assert_eq!(load_error("23", "11", "4", "4"), "invalid parameters: g and h must differ");
+}
+
+#[test]
+fn test_random_challenge_respects_bit_length() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    for _ in 0..100 {
+        assert!(random_challenge(&params, Some(16)) < BigInt::from(1 << 16));
+        assert!(random_challenge(&params, None) < params.q);
+    }
+    // A bit length beyond the order falls back to the full range
+    assert!(random_challenge(&params, Some(1024)) < params.q);
+}
+
+#[test]
+fn test_random_challenges_differ() {
+    let params = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let challenges: std::collections::HashSet<BigInt> = (0..100).map(|_| random_challenge(&params, None)).collect();
+    assert_eq!(challenges.len(), 100);
+}
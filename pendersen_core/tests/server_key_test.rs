@@ -0,0 +1,77 @@
+use pendersen_core::server_key::{verify, LoginRecord};
+use pendersen_core::{Group, GroupParameters, RistrettoParameters, ServerKey, DEFAULT_PARAMETERS_PATH};
+
+// Check a fresh proof over a login against `public`, and against the same login
+// with the session token changed
+fn check<G: Group>(group: &G, key: &ServerKey<G>, public: &G::Element) -> (bool, bool) {
+    let (r1, r2) = (group.exp(&group.g(), &group.random_scalar()), group.exp(&group.h(), &group.random_scalar()));
+    let (c, s) = (group.random_scalar(), group.random_scalar());
+    let login = LoginRecord {
+        user: "alice",
+        commitment: (&r1, &r2),
+        challenge: &c,
+        response: &s,
+        key_shares: None,
+        session_id: "session",
+        expires_at: 1_700_000_000,
+    };
+    let proof = key.prove(group, &login);
+
+    let swapped = LoginRecord { session_id: "other", ..login };
+    (verify(group, public, &login, &proof), verify(group, public, &swapped, &proof))
+}
+
+#[test]
+fn test_server_proof_verifies_under_its_key() {
+    let group = RistrettoParameters::default();
+    let key = ServerKey::generate(&group);
+    assert_eq!(check(&group, &key, key.public()), (true, false));
+
+    let group = GroupParameters::load(DEFAULT_PARAMETERS_PATH).unwrap();
+    let key = ServerKey::generate(&group);
+    assert_eq!(check(&group, &key, key.public()), (true, false));
+}
+
+#[test]
+fn test_server_proof_fails_under_another_key() {
+    let group = RistrettoParameters::default();
+    let key = ServerKey::generate(&group);
+    let impostor = ServerKey::generate(&group);
+
+    assert_eq!(check(&group, &impostor, key.public()), (false, false));
+}
+
+#[test]
+fn test_server_proof_covers_the_key_exchange() {
+    let group = RistrettoParameters::default();
+    let key = ServerKey::generate(&group);
+    let (r1, r2, c, s) = (group.g(), group.h(), group.random_scalar(), group.random_scalar());
+    let (client_share, server_share) = (group.exp(&group.g(), &c), group.exp(&group.g(), &s));
+    let login = LoginRecord {
+        user: "alice",
+        commitment: (&r1, &r2),
+        challenge: &c,
+        response: &s,
+        key_shares: Some((&client_share, &server_share)),
+        session_id: "session",
+        expires_at: 1_700_000_000,
+    };
+
+    let proof = key.prove(&group, &login);
+
+    assert!(verify(&group, key.public(), &login, &proof));
+    let swapped = LoginRecord { key_shares: Some((&client_share, &client_share)), ..login };
+    assert!(!verify(&group, key.public(), &swapped, &proof));
+    let dropped = LoginRecord { key_shares: None, ..login };
+    assert!(!verify(&group, key.public(), &dropped, &proof));
+}
+
+#[test]
+fn test_server_key_from_seed_is_deterministic() {
+    let group = RistrettoParameters::default();
+
+    let key = ServerKey::from_seed(&group, b"seed");
+
+    assert!(*key.public() == *ServerKey::from_seed(&group, b"seed").public());
+    assert!(*key.public() != *ServerKey::from_seed(&group, b"other seed").public());
+}
@@ -0,0 +1,61 @@
+use pendersen_core::primes::is_probable_prime;
+use pendersen_core::standard::{find, STANDARD_SETS};
+use pendersen_core::{Backend, Group, ParameterSource};
+use num_bigint::BigInt;
+use std::path::PathBuf;
+
+#[test]
+fn test_standard_sets_are_safe_prime_groups() {
+    for set in STANDARD_SETS {
+        let params = set.parameters();
+        assert_eq!(params.p, 2 * &params.q + 1, "{}", set.name);
+        assert!(params.is_member(&params.g) && params.is_member(&params.h), "{}", set.name);
+        assert_ne!(params.h, BigInt::from(1), "{}", set.name);
+        assert_ne!(params.g, params.h, "{}", set.name);
+    }
+}
+
+#[test]
+fn test_standard_primes_pass_validation() {
+    for name in ["modp2048", "ffdhe2048"] {
+        let params = find(name).unwrap().parameters();
+        assert!(is_probable_prime(&params.p, 4) && is_probable_prime(&params.q, 4), "{}", name);
+        assert!(params.validate().is_ok(), "{}", name);
+    }
+}
+
+#[test]
+fn test_standard_set_sizes() {
+    for set in STANDARD_SETS {
+        let bits: u64 = set.name.trim_start_matches(char::is_alphabetic).parse().unwrap();
+        assert_eq!(set.parameters().p.bits(), bits, "{}", set.name);
+    }
+}
+
+#[test]
+fn test_h_is_derived_from_the_set_name() {
+    let first = find("ffdhe3072").unwrap().parameters();
+    let again = find("ffdhe3072").unwrap().parameters();
+    assert_eq!(first.h, again.h);
+    assert_ne!(first.h, find("modp3072").unwrap().parameters().h);
+}
+
+#[test]
+fn test_parameter_source_parsing() {
+    assert_eq!(ParameterSource::parse("ffdhe3072"), ParameterSource::Standard("ffdhe3072"));
+    assert_eq!(ParameterSource::parse("ristretto255"), ParameterSource::Standard("ristretto255"));
+    assert_eq!(ParameterSource::parse("../parameters.json"), ParameterSource::File(PathBuf::from("../parameters.json")));
+    assert_eq!(ParameterSource::parse("ffdhe3072").to_string(), "built-in set ffdhe3072 (RFC 7919)");
+    assert!(ParameterSource::standard_names().any(|name| name == "modp4096"));
+}
+
+#[test]
+fn test_parameter_source_loading() {
+    assert!(matches!(ParameterSource::parse("ristretto255").load().unwrap(), Backend::Ristretto255(_)));
+    match ParameterSource::parse("ffdhe2048").load().unwrap() {
+        Backend::ModP(params) => assert_eq!(params.p.bits(), 2048),
+        other => panic!("expected modp, got {}", other.name()),
+    }
+    assert!(ParameterSource::parse("../parameters.json").load().is_ok());
+    assert!(ParameterSource::parse("no-such-file.json").load().is_err());
+}
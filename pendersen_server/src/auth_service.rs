@@ -1,81 +1,418 @@
-use tonic::{Request, Response, Status};
-use num_bigint::{BigInt, Sign};
-use rand::Rng;
-use serde::Deserialize;
-use std::{collections::HashMap, fs, str::FromStr, sync::{Arc, Mutex}};
-
-use crate::pb::{RegisterRequest, RegisterResponse, AuthenticationChallengeRequest, AuthenticationChallengeResponse, AuthenticationAnswerRequest, AuthenticationAnswerResponse};
-
-#[derive(Deserialize)]
-struct Params {
-    p: String,
-    q: String,
-    g: String,
-    h: String,
-}
-
-pub struct AuthService {
-    users: Arc<Mutex<HashMap<String, UserPublicParameters>>>,
-    sessions: Arc<Mutex<HashMap<String, SessionParameters>>>,
-    g: BigInt,
-    h: BigInt,
-    p: BigInt,
-    q: BigInt, // Consider using this field, or remove it if unnecessary
+use tonic::{metadata::MetadataValue, Request, Response, Status};
+use pendersen_core::server_key::LoginRecord;
+use pendersen_core::{key_exchange, protocol, DecodeError, Group, KdfParameters, KeyShare, ServerKey, SessionKey, Transcript};
+use rand::{rngs::OsRng, Rng};
+use std::{sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::pb::{RegisterRequest, RegisterResponse, AuthenticationChallengeRequest, AuthenticationChallengeResponse, AuthenticationAnswerRequest, AuthenticationAnswerResponse, LoginRequest, LoginResponse, KdfParametersRequest, KdfParametersResponse, ValidateSessionRequest, ValidateSessionResponse, ChangeCredentialsRequest, ChangeCredentialsResponse, ServerProof};
+use crate::challenge_token::{ChallengeTokens, TokenError};
+use crate::lockout::{AttemptTracker, LockoutPolicy, RETRY_AFTER_METADATA};
+use crate::session_registry::{SessionRegistry, DEFAULT_SESSION_TTL};
+use crate::session_store::{MemorySessionStore, SessionStore, StoredChallenge};
+use crate::tls::{ChannelBinding, ConnectionInfo};
+use crate::user_store::{MemoryUserStore, StoreError, StoredUser, UserStore};
+
+// How far a non-interactive login timestamp may drift from the server clock
+pub const DEFAULT_LOGIN_WINDOW: Duration = Duration::from_secs(60);
+
+// How long an interactive challenge may stay unanswered
+pub const DEFAULT_CHALLENGE_TTL: Duration = Duration::from_secs(60);
+
+// Domain separator for the id a used login proof is remembered under
+const LOGIN_PROOF_DOMAIN: &[u8] = b"pendersen-project/login-proof-id/v1";
+
+pub struct AuthService<G: Group> {
+    users: Arc<dyn UserStore>,
+    // Pending challenges; the session registry issues its tokens into the same store
+    sessions: Arc<dyn SessionStore>,
+    group: G,
+    login_window: Duration,
+    challenge_bits: Option<u64>,
+    challenge_ttl: Duration,
+    // When set, challenges travel inside the auth_id instead of the session store
+    challenge_tokens: Option<Arc<ChallengeTokens>>,
+    session_registry: SessionRegistry,
+    // Failed proofs per user, for lockout with exponential backoff
+    attempts: Arc<AttemptTracker>,
+    // Long-term key successful logins are signed with, for clients to authenticate us
+    server_key: Option<ServerKey<G>>,
 }
 
 #[derive(Clone)]
-struct UserPublicParameters {
-    y1: BigInt,
-    y2: BigInt,
+struct UserPublicParameters<G: Group> {
+    y1: G::Element,
+    y2: G::Element,
+    kdf: Option<KdfParameters>,
 }
 
-#[derive(Clone)]
-struct SessionParameters {
+struct SessionParameters<G: Group> {
     user: String,
-    r1: BigInt,
-    r2: BigInt,
-    c: BigInt,
+    r1: G::Element,
+    r2: G::Element,
+    c: G::Scalar,
 }
 
-impl AuthService {
-    pub fn new(g: BigInt, h: BigInt, p: BigInt, q: BigInt) -> Self {
+impl<G: Group> AuthService<G> {
+    pub fn new(group: G) -> Self {
+        let sessions: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::new());
         AuthService {
-            users: Arc::new(Mutex::new(HashMap::new())),
-            sessions: Arc::new(Mutex::new(HashMap::new())),
-            g,
-            h,
-            p,
-            q,
+            users: Arc::new(MemoryUserStore::new()),
+            sessions: sessions.clone(),
+            group,
+            login_window: DEFAULT_LOGIN_WINDOW,
+            challenge_bits: None,
+            challenge_ttl: DEFAULT_CHALLENGE_TTL,
+            challenge_tokens: None,
+            session_registry: SessionRegistry::new(sessions, DEFAULT_SESSION_TTL),
+            attempts: Arc::new(AttemptTracker::default()),
+            server_key: None,
+        }
+    }
+
+    pub fn with_user_store(mut self, users: Arc<dyn UserStore>) -> Self {
+        self.users = users;
+        self
+    }
+
+    // Where pending challenges and issued sessions live; share a persistent
+    // store between processes to let them serve each other's logins
+    pub fn with_session_store(mut self, sessions: Arc<dyn SessionStore>) -> Self {
+        self.session_registry = SessionRegistry::new(sessions.clone(), self.session_registry.ttl());
+        self.sessions = sessions;
+        self
+    }
+
+    pub fn with_login_window(mut self, login_window: Duration) -> Self {
+        self.login_window = login_window;
+        self
+    }
+
+    // Draw interactive challenges from [0, 2^bits) instead of [0, q)
+    pub fn with_challenge_bits(mut self, challenge_bits: u64) -> Self {
+        self.challenge_bits = Some(challenge_bits);
+        self
+    }
+
+    pub fn with_challenge_ttl(mut self, challenge_ttl: Duration) -> Self {
+        self.challenge_ttl = challenge_ttl;
+        self
+    }
+
+    // Seal pending challenges into the auth_id so that any replica holding the
+    // same keys can answer them; nothing is written to the session store
+    pub fn with_challenge_tokens(mut self, tokens: Arc<ChallengeTokens>) -> Self {
+        self.challenge_tokens = Some(tokens);
+        self
+    }
+
+    // How long session tokens issued on a successful login stay valid
+    pub fn with_session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_registry = SessionRegistry::new(self.sessions.clone(), ttl);
+        self
+    }
+
+    // When to lock a user out after failed proofs and for how long
+    pub fn with_lockout_policy(mut self, policy: LockoutPolicy) -> Self {
+        self.attempts = Arc::new(AttemptTracker::new(policy));
+        self
+    }
+
+    // Prove every successful login with this key, so that clients pinning its
+    // public half know they reached this server and not an impostor
+    pub fn with_server_key(mut self, server_key: ServerKey<G>) -> Self {
+        self.server_key = Some(server_key);
+        self
+    }
+
+    pub fn group(&self) -> &G {
+        &self.group
+    }
+
+    // Drop unanswered challenges past their TTL, and forget redeemed challenge
+    // tokens and used login proofs that have expired, returning how many entries
+    // were evicted
+    pub fn reap_expired_challenges(&self) -> Result<usize, StoreError> {
+        let now = SystemTime::now();
+        let spent = self.challenge_tokens.as_ref().map_or(0, |tokens| tokens.purge_spent(now));
+        Ok(self.sessions.purge_expired_challenges(now)? + self.sessions.purge_expired_proofs(now)? + spent)
+    }
+
+    // Evict expired challenges and sessions every `interval` on the tokio runtime
+    pub fn spawn_challenge_reaper(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let sessions = Arc::clone(&self.sessions);
+        let challenge_tokens = self.challenge_tokens.clone();
+        let attempts = Arc::clone(&self.attempts);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let now = SystemTime::now();
+                if let Some(tokens) = &challenge_tokens {
+                    tokens.purge_spent(now);
+                }
+                attempts.purge(now);
+                if let Err(e) = sessions
+                    .purge_expired_challenges(now)
+                    .and_then(|_| sessions.purge_expired_proofs(now))
+                    .and_then(|_| sessions.purge_expired_sessions(now))
+                {
+                    log::error!("Failed to evict expired login state: {}", e);
+                }
+            }
+        })
+    }
+
+    fn decode_element(&self, field: &str, value: &str) -> Result<G::Element, Status> {
+        self.group.decode_element(value).map_err(|e| invalid_field(field, e))
+    }
+
+    fn decode_scalar(&self, field: &str, value: &str) -> Result<G::Scalar, Status> {
+        self.group.decode_scalar(value).map_err(|e| invalid_field(field, e))
+    }
+
+    fn user(&self, user: &str) -> Result<UserPublicParameters<G>, Status> {
+        match self.users.get(user).map_err(store_error)? {
+            Some(stored) => self.decode_user(stored),
+            None => Err(Status::not_found("User not found")),
+        }
+    }
+
+    // Stored keys were checked on the way in, so failing to decode them means
+    // the store is damaged or was written with different group parameters
+    fn decode_user(&self, stored: StoredUser) -> Result<UserPublicParameters<G>, Status> {
+        let decode = |value: &str| {
+            self.group
+                .decode_element(value)
+                .map_err(|e| Status::internal(format!("stored key does not decode: {}", e)))
+        };
+        Ok(UserPublicParameters {
+            y1: decode(&stored.y1)?,
+            y2: decode(&stored.y2)?,
+            kdf: stored.kdf,
+        })
+    }
+
+    fn decode_challenge(&self, challenge: StoredChallenge) -> Result<SessionParameters<G>, Status> {
+        let corrupt = |e: DecodeError| Status::internal(format!("stored challenge does not decode: {}", e));
+        Ok(SessionParameters {
+            r1: self.group.decode_element(&challenge.r1).map_err(corrupt)?,
+            r2: self.group.decode_element(&challenge.r2).map_err(corrupt)?,
+            c: self.group.decode_scalar(&challenge.c).map_err(corrupt)?,
+            user: challenge.user,
+        })
+    }
+
+    fn encode_user(&self, user: &UserPublicParameters<G>) -> StoredUser {
+        StoredUser {
+            y1: self.group.encode_element(&user.y1),
+            y2: self.group.encode_element(&user.y2),
+            kdf: user.kdf.clone(),
+        }
+    }
+
+    // Reject login timestamps further than the login window from now
+    fn check_freshness(&self, timestamp: u64) -> Result<(), Status> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.abs_diff(timestamp) > self.login_window.as_secs() {
+            return Err(Status::invalid_argument("timestamp is outside the accepted login window"));
+        }
+        Ok(())
+    }
+
+    // Accept a verified login proof only once. It is remembered until its
+    // timestamp leaves the login window, after which it would be refused anyway.
+    fn spend_login_proof(&self, user: &str, (r1, r2): (&G::Element, &G::Element), timestamp: u64) -> Result<(), Status> {
+        let mut transcript = Transcript::new(LOGIN_PROOF_DOMAIN);
+        transcript.append_group(&self.group);
+        transcript.append(b"user", user.as_bytes());
+        transcript.append_element(&self.group, b"r1", r1);
+        transcript.append_element(&self.group, b"r2", r2);
+        transcript.append(b"timestamp", &timestamp.to_be_bytes());
+        let expires_at = UNIX_EPOCH + Duration::from_secs(timestamp) + self.login_window + Duration::from_secs(1);
+        if !self
+            .sessions
+            .record_proof(&hex::encode(transcript.digest()), expires_at)
+            .map_err(store_error)?
+        {
+            log::warn!("Replayed login proof for user: {}", user);
+            return Err(Status::unauthenticated("Login proof has already been used"));
+        }
+        Ok(())
+    }
+
+    // The challenge an answer must be for: bound to the client's key share when
+    // it sent one, then to the TLS connection it arrived on. Requests on
+    // plaintext or in-process connections have no exporter to bind to.
+    fn answered_challenge(&self, c: G::Scalar, client_share: Option<&G::Element>, binding: Option<&ChannelBinding>) -> G::Scalar {
+        let c = match client_share {
+            Some(share) => key_exchange::bind_key_share(&self.group, &c, share),
+            None => c,
+        };
+        match binding {
+            Some(binding) => protocol::bind_challenge(&self.group, &c, &binding.0),
+            None => c,
+        }
+    }
+
+    // An empty client_share means the client did not ask for a key exchange.
+    // The client can only tell our share from an attacker's over TLS or with a
+    // server key to prove it, so refuse the exchange without either
+    fn decode_client_share(&self, value: &str, binding: Option<&ChannelBinding>) -> Result<Option<G::Element>, Status> {
+        if value.is_empty() {
+            return Ok(None);
+        }
+        if binding.is_none() && self.server_key.is_none() {
+            return Err(Status::failed_precondition("Key exchange needs TLS or a server key"));
+        }
+        let share = self.decode_element("client_share", value)?;
+        if share == self.group.identity() {
+            return Err(Status::invalid_argument("invalid client_share: the identity is not a key share"));
+        }
+        Ok(Some(share))
+    }
+
+    // Once the proof has verified, answer the client's key share with a fresh one
+    // of ours and derive the session key; logins without a key share get neither
+    fn key_exchange(
+        &self,
+        user: &str,
+        (r1, r2): (&G::Element, &G::Element),
+        c: &G::Scalar,
+        client_share: Option<&G::Element>,
+        verified: bool,
+    ) -> Result<Option<(G::Element, SessionKey)>, Status> {
+        let client_share = match client_share {
+            Some(share) if verified => share,
+            _ => return Ok(None),
+        };
+        let server_share = KeyShare::generate(&self.group);
+        let shared = server_share
+            .agree(&self.group, client_share)
+            .ok_or_else(|| Status::invalid_argument("invalid client_share: no shared secret"))?;
+        let key = key_exchange::session_key(&self.group, user, (r1, r2), c, (client_share, server_share.public()), &shared);
+        Ok(Some((server_share.public().clone(), key)))
+    }
+
+    // Our proof over a successful login, when we have a long-term key
+    fn server_proof(&self, login: &LoginRecord<'_, G>) -> Option<ServerProof> {
+        self.server_key.as_ref().map(|server_key| {
+            let proof = server_key.prove(&self.group, login);
+            ServerProof {
+                r: self.group.encode_element(&proof.r),
+                s: self.group.encode_scalar(&proof.s),
+            }
+        })
+    }
+
+    // Refuse to look at proofs from a user who is locked out
+    fn check_lockout(&self, user: &str) -> Result<(), Status> {
+        self.attempts.check(user, SystemTime::now()).map_err(locked_out)
+    }
+
+    // Check the lockout and count the proof about to be verified as failed in one
+    // step; record_attempt clears the count again if it verifies
+    fn begin_attempt(&self, user: &str) -> Result<(), Status> {
+        self.attempts.begin_attempt(user, SystemTime::now()).map_err(locked_out)
+    }
+
+    fn record_attempt(&self, user: &str, verified: bool) {
+        if verified {
+            self.attempts.record_success(user);
+        } else if let Err(delay) = self.attempts.check(user, SystemTime::now()) {
+            log::warn!("Locking out user {} for {}s", user, delay.as_secs());
+        }
+    }
+
+    // Issue a session token and its Unix expiry on success; UNAUTHENTICATED on a bad proof
+    fn session_id(&self, user: &str, verified: bool, key: Option<SessionKey>) -> Result<(String, u64), Status> {
+        self.record_attempt(user, verified);
+        if verified {
+            log::info!("Verification successful for user: {}", user);
+            let (token, session) = self.session_registry.issue(user, key).map_err(store_error)?;
+            Ok((token, session.expires_at_unix()))
+        } else {
+            log::warn!("Verification failed for user: {}", user);
+            Err(Status::unauthenticated("Proof verification failed"))
         }
     }
+}
+
+// 128-bit random identifier for a pending challenge, hex encoded
+fn new_auth_id() -> String {
+    format!("{:032x}", OsRng.gen::<u128>())
+}
+
+// Salt and cost parameters the client derived x with, if it used the KDF
+fn validate_kdf(kdf: Option<crate::pb::KdfParameters>) -> Result<Option<KdfParameters>, Status> {
+    let kdf = kdf.map(KdfParameters::from);
+    if let Some(kdf) = &kdf {
+        kdf.validate().map_err(|e| Status::invalid_argument(e.to_string()))?;
+    }
+    Ok(kdf)
+}
 
-    pub fn load_parameters() -> Result<(BigInt, BigInt, BigInt, BigInt), Box<dyn std::error::Error>> {
-        let file_content = fs::read_to_string("../parameters.json")?;
-        let params: Params = serde_json::from_str(&file_content)?;
+fn store_error(error: StoreError) -> Status {
+    Status::internal(error.to_string())
+}
 
-        let p = BigInt::from_str(&params.p)?;
-        let q = BigInt::from_str(&params.q)?;
-        let g = BigInt::from_str(&params.g)?;
-        let h = BigInt::from_str(&params.h)?;
+// RESOURCE_EXHAUSTED with the whole seconds left in the retry-after metadata
+fn locked_out(remaining: Duration) -> Status {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    let mut status = Status::resource_exhausted(format!("Too many failed attempts; retry in {} seconds", seconds));
+    status
+        .metadata_mut()
+        .insert(RETRY_AFTER_METADATA, MetadataValue::from(seconds));
+    status
+}
 
-        Ok((p, q, g, h))
+// A replayed token looks like an unknown auth_id; anything else is a bad argument
+fn token_error(error: TokenError) -> Status {
+    match error {
+        TokenError::Spent => Status::not_found("Session not found"),
+        error => Status::invalid_argument(format!("invalid auth_id: {}", error)),
     }
 }
 
+// Exporter value of the TLS connection a request arrived on, if any
+fn channel_binding<T>(request: &Request<T>) -> Option<ChannelBinding> {
+    request
+        .extensions()
+        .get::<ConnectionInfo>()
+        .map(|info| info.channel_binding.clone())
+}
+
+fn invalid_field(field: &str, error: DecodeError) -> Status {
+    Status::invalid_argument(format!("invalid {}: {}", field, error))
+}
+
 #[tonic::async_trait]
-impl crate::pb::auth_server::Auth for AuthService {
+impl<G: Group> crate::pb::auth_server::Auth for AuthService<G> {
     async fn register(
         &self,
         request: Request<RegisterRequest>,
     ) -> Result<Response<RegisterResponse>, Status> {
         let req = request.into_inner();
-        println!("Received registration for user: {}", req.user);
+        log::info!("Received registration for user: {}", req.user);
+
+        let y1 = self.decode_element("y1", &req.y1)?;
+        let y2 = self.decode_element("y2", &req.y2)?;
 
-        let y1 = BigInt::from_str(&req.y1).unwrap_or_default();
-        let y2 = BigInt::from_str(&req.y2).unwrap_or_default();
+        let r1 = self.decode_element("r1", &req.r1)?;
+        let r2 = self.decode_element("r2", &req.r2)?;
+        let s = self.decode_scalar("s", &req.s)?;
+        let kdf = validate_kdf(req.kdf)?;
 
-        let user_params = UserPublicParameters { y1, y2 };
-        self.users.lock().unwrap().insert(req.user.clone(), user_params);
+        // Only store keys whose owner proved knowledge of x with y1 = g^x and y2 = h^x
+        let c = protocol::registration_challenge(&self.group, &req.user, (&y1, &y2), (&r1, &r2), kdf.as_ref());
+        if !protocol::verify(&self.group, (&y1, &y2), (&r1, &r2), &c, &s) {
+            return Err(Status::invalid_argument("registration proof does not verify"));
+        }
+
+        // Never replace an existing user here; that takes ChangeCredentials
+        let user_params = self.encode_user(&UserPublicParameters { y1, y2, kdf });
+        if !self.users.insert_if_absent(&req.user, &user_params).map_err(store_error)? {
+            return Err(Status::already_exists("User already registered"));
+        }
 
         let response = RegisterResponse {
             message: format!("User {} registered successfully", req.user),
@@ -84,31 +421,56 @@ impl crate::pb::auth_server::Auth for AuthService {
         Ok(Response::new(response))
     }
 
+    async fn get_kdf_parameters(
+        &self,
+        request: Request<KdfParametersRequest>,
+    ) -> Result<Response<KdfParametersResponse>, Status> {
+        let req = request.into_inner();
+        let user_params = self.user(&req.user)?;
+
+        let response = KdfParametersResponse {
+            kdf: user_params.kdf.map(Into::into),
+        };
+
+        Ok(Response::new(response))
+    }
+
     async fn create_authentication_challenge(
         &self,
         request: Request<AuthenticationChallengeRequest>,
     ) -> Result<Response<AuthenticationChallengeResponse>, Status> {
         let req = request.into_inner();
-        println!("Received authentication challenge for user: {}", req.user);
+        log::info!("Received authentication challenge for user: {}", req.user);
+
+        let r1 = self.decode_element("r1", &req.r1)?;
+        let r2 = self.decode_element("r2", &req.r2)?;
 
-        let r1 = BigInt::from_str(&req.r1).unwrap_or_default();
-        let r2 = BigInt::from_str(&req.r2).unwrap_or_default();
+        // Only issue challenges for users that can answer them
+        self.user(&req.user)?;
+        self.check_lockout(&req.user)?;
 
-        let c = BigInt::from(rand::thread_rng().gen_range(1..10000)); // Random challenge
-        let auth_id = "auth123".to_string();
+        let c = protocol::random_challenge(&self.group, self.challenge_bits);
 
-        let session_params = SessionParameters {
+        let challenge = StoredChallenge {
             user: req.user.clone(),
-            r1,
-            r2,
-            c: c.clone(),
+            r1: self.group.encode_element(&r1),
+            r2: self.group.encode_element(&r2),
+            c: self.group.encode_scalar(&c),
+            expires_at: SystemTime::now() + self.challenge_ttl,
         };
 
-        self.sessions.lock().unwrap().insert(auth_id.clone(), session_params);
+        let auth_id = match &self.challenge_tokens {
+            Some(tokens) => tokens.seal(&challenge),
+            None => {
+                let auth_id = new_auth_id();
+                self.sessions.put_challenge(&auth_id, &challenge).map_err(store_error)?;
+                auth_id
+            }
+        };
 
         let response = AuthenticationChallengeResponse {
             auth_id,
-            c: c.to_string(),
+            c: self.group.encode_scalar(&c),
         };
 
         Ok(Response::new(response))
@@ -118,58 +480,212 @@ impl crate::pb::auth_server::Auth for AuthService {
         &self,
         request: Request<AuthenticationAnswerRequest>,
     ) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let binding = channel_binding(&request);
         let req = request.into_inner();
-        let sessions = self.sessions.lock().unwrap();
-        let session = match sessions.get(&req.auth_id) {
-            Some(session) => session,
-            None => return Err(Status::not_found("Session not found")),
+        // Challenges are single use: remove it before looking at the answer
+        let challenge = match &self.challenge_tokens {
+            Some(tokens) => tokens.redeem(&req.auth_id).map_err(token_error)?,
+            None => match self.sessions.take_challenge(&req.auth_id).map_err(store_error)? {
+                Some(challenge) => challenge,
+                None => return Err(Status::not_found("Session not found")),
+            },
         };
+        if challenge.expires_at <= SystemTime::now() {
+            return Err(Status::failed_precondition("Challenge has expired"));
+        }
+        let session = self.decode_challenge(challenge)?;
 
-        let users = self.users.lock().unwrap();
-        let user_params = match users.get(&session.user) {
-            Some(params) => params,
-            None => return Err(Status::not_found("User not found")),
+        // The challenge outlived the user it was issued to
+        let user_params = match self.users.get(&session.user).map_err(store_error)? {
+            Some(stored) => self.decode_user(stored)?,
+            None => return Err(Status::failed_precondition("User for this challenge is no longer registered")),
+        };
+        let s = self.decode_scalar("s", &req.s)?;
+        let client_share = self.decode_client_share(&req.client_share, binding.as_ref())?;
+        self.begin_attempt(&session.user)?;
+
+        // Check g^s * y1^c == r1 and h^s * y2^c == r2, with c bound to the key share and connection
+        let c = self.answered_challenge(session.c, client_share.as_ref(), binding.as_ref());
+        let verified = protocol::verify(
+            &self.group,
+            (&user_params.y1, &user_params.y2),
+            (&session.r1, &session.r2),
+            &c,
+            &s,
+        );
+
+        let exchange =
+            self.key_exchange(&session.user, (&session.r1, &session.r2), &c, client_share.as_ref(), verified)?;
+        let (session_id, expires_at) =
+            self.session_id(&session.user, verified, exchange.as_ref().map(|(_, key)| key.clone()))?;
+        let server_share = exchange.map(|(share, _)| share);
+
+        let server_proof = self.server_proof(&LoginRecord {
+            user: &session.user,
+            commitment: (&session.r1, &session.r2),
+            challenge: &c,
+            response: &s,
+            key_shares: client_share.as_ref().zip(server_share.as_ref()),
+            session_id: &session_id,
+            expires_at,
+        });
+        let response = AuthenticationAnswerResponse {
+            session_id,
+            expires_at,
+            server_share: server_share.map(|share| self.group.encode_element(&share)).unwrap_or_default(),
+            server_proof,
         };
 
-        let s = BigInt::from_str(&req.s).unwrap_or_default();
+        Ok(Response::new(response))
+    }
 
-        // Ensure exponents are positive
-        if s.sign() == Sign::Minus {
-            return Err(Status::invalid_argument("Negative exponentiation is not allowed"));
+    async fn login(
+        &self,
+        request: Request<LoginRequest>,
+    ) -> Result<Response<LoginResponse>, Status> {
+        let binding = channel_binding(&request);
+        let req = request.into_inner();
+        log::info!("Received non-interactive login for user: {}", req.user);
+
+        let r1 = self.decode_element("r1", &req.r1)?;
+        let r2 = self.decode_element("r2", &req.r2)?;
+        let s = self.decode_scalar("s", &req.s)?;
+        let client_share = self.decode_client_share(&req.client_share, binding.as_ref())?;
+        self.check_freshness(req.timestamp)?;
+
+        let user_params = self.user(&req.user)?;
+        self.begin_attempt(&req.user)?;
+
+        // Recompute c = H(parameters, user, y1, y2, r1, r2, timestamp) instead of storing it
+        let c = protocol::login_challenge(
+            &self.group,
+            &req.user,
+            (&user_params.y1, &user_params.y2),
+            (&r1, &r2),
+            &req.timestamp.to_be_bytes(),
+        );
+        let c = self.answered_challenge(c, client_share.as_ref(), binding.as_ref());
+        let verified = protocol::verify(
+            &self.group,
+            (&user_params.y1, &user_params.y2),
+            (&r1, &r2),
+            &c,
+            &s,
+        );
+
+        if verified {
+            self.spend_login_proof(&req.user, (&r1, &r2), req.timestamp)?;
         }
 
-        // Calculate A' = g^s * y1^c mod p
-        let r1p = pow_mod(&self.g, &s, &user_params.y1, &session.c, &self.p);
-
-        // Calculate B' = h^s * y2^c mod p
-        let r2p = pow_mod(&self.h, &s, &user_params.y2, &session.c, &self.p);
+        let exchange = self.key_exchange(&req.user, (&r1, &r2), &c, client_share.as_ref(), verified)?;
+        let (session_id, expires_at) = self.session_id(&req.user, verified, exchange.as_ref().map(|(_, key)| key.clone()))?;
+        let server_share = exchange.map(|(share, _)| share);
+
+        let server_proof = self.server_proof(&LoginRecord {
+            user: &req.user,
+            commitment: (&r1, &r2),
+            challenge: &c,
+            response: &s,
+            key_shares: client_share.as_ref().zip(server_share.as_ref()),
+            session_id: &session_id,
+            expires_at,
+        });
+        let response = LoginResponse {
+            session_id,
+            expires_at,
+            server_share: server_share.map(|share| self.group.encode_element(&share)).unwrap_or_default(),
+            server_proof,
+        };
 
-        // Retrieve R1 and R2 for comparison
-        let r1 = &session.r1;
-        let r2 = &session.r2;
+        Ok(Response::new(response))
+    }
 
-        let session_id = if r1p == *r1 && r2p == *r2 {
-            println!("Verification successful for user: {}", session.user);
-            "session123".to_string() // Indicate success
-        } else {
-            println!("Verification failed for user: {}", session.user);
-            "".to_string() // Indicate failure
+    async fn validate_session(
+        &self,
+        request: Request<ValidateSessionRequest>,
+    ) -> Result<Response<ValidateSessionResponse>, Status> {
+        let req = request.into_inner();
+        let session = self
+            .session_registry
+            .validate(&req.session_id)
+            .map_err(store_error)?
+            .ok_or_else(|| Status::unauthenticated("Session is invalid or has expired"))?;
+
+        let response = ValidateSessionResponse {
+            expires_at: session.expires_at_unix(),
+            user: session.user,
         };
 
-        let response = AuthenticationAnswerResponse { session_id };
-
         Ok(Response::new(response))
     }
-}
 
-// Modular exponentiation function
-fn pow_mod(g: &BigInt, s: &BigInt, y: &BigInt, c: &BigInt, p: &BigInt) -> BigInt {
-    let base1 = g.modpow(s, p);
-    let base2 = y.modpow(c, p);
-    let result = (base1 * base2) % p;
-    if result.sign() == Sign::Minus {
-        result + p
-    } else {
-        result
+    async fn change_credentials(
+        &self,
+        request: Request<ChangeCredentialsRequest>,
+    ) -> Result<Response<ChangeCredentialsResponse>, Status> {
+        let binding = channel_binding(&request);
+        let req = request.into_inner();
+        log::info!("Received credential change for user: {}", req.user);
+
+        let r1 = self.decode_element("r1", &req.r1)?;
+        let r2 = self.decode_element("r2", &req.r2)?;
+        let s = self.decode_scalar("s", &req.s)?;
+        let new_y1 = self.decode_element("new_y1", &req.new_y1)?;
+        let new_y2 = self.decode_element("new_y2", &req.new_y2)?;
+        let new_r1 = self.decode_element("new_r1", &req.new_r1)?;
+        let new_r2 = self.decode_element("new_r2", &req.new_r2)?;
+        let new_s = self.decode_scalar("new_s", &req.new_s)?;
+        let kdf = validate_kdf(req.kdf)?;
+        self.check_freshness(req.timestamp)?;
+
+        // As at registration, only accept a new key whose owner knows x with new_y1 = g^x and new_y2 = h^x
+        let new_c = protocol::registration_challenge(&self.group, &req.user, (&new_y1, &new_y2), (&new_r1, &new_r2), kdf.as_ref());
+        if !protocol::verify(&self.group, (&new_y1, &new_y2), (&new_r1, &new_r2), &new_c, &new_s) {
+            return Err(Status::invalid_argument("new key proof does not verify"));
+        }
+
+        let user_params = self.user(&req.user)?;
+        self.begin_attempt(&req.user)?;
+
+        // The proof is with the old secret and commits to the replacement key
+        let c = protocol::change_credentials_challenge(
+            &self.group,
+            &req.user,
+            (&user_params.y1, &user_params.y2),
+            (&r1, &r2),
+            (&new_y1, &new_y2),
+            kdf.as_ref(),
+            &req.timestamp.to_be_bytes(),
+        );
+        let c = self.answered_challenge(c, None, binding.as_ref());
+        let verified = protocol::verify(
+            &self.group,
+            (&user_params.y1, &user_params.y2),
+            (&r1, &r2),
+            &c,
+            &s,
+        );
+        self.record_attempt(&req.user, verified);
+        if !verified {
+            log::warn!("Credential change failed for user: {}", req.user);
+            return Err(Status::unauthenticated("Proof verification failed"));
+        }
+
+        // Only swap if nobody, in this process or another sharing the store,
+        // changed the credentials while we verified
+        let replacement = self.encode_user(&UserPublicParameters { y1: new_y1, y2: new_y2, kdf });
+        if !self
+            .users
+            .replace(&req.user, &self.encode_user(&user_params), &replacement)
+            .map_err(store_error)?
+        {
+            return Err(Status::aborted("Credentials changed concurrently"));
+        }
+
+        let response = ChangeCredentialsResponse {
+            message: format!("Credentials for user {} changed successfully", req.user),
+        };
+
+        Ok(Response::new(response))
     }
 }
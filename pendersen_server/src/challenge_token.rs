@@ -0,0 +1,289 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    path::Path,
+    sync::{Mutex, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::session_store::StoredChallenge;
+
+const TOKEN_VERSION: u8 = 1;
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = 1 + KEY_ID_LEN;
+
+// Bound into every token as associated data, together with the header
+const TOKEN_DOMAIN: &[u8] = b"pendersen-project/challenge-token/v1";
+
+// A 256-bit XChaCha20-Poly1305 key for challenge tokens. Its id, a hash of
+// the key, travels in the clear so a token names the key that opens it.
+#[derive(Clone)]
+pub struct ChallengeKey {
+    id: [u8; KEY_ID_LEN],
+    cipher: XChaCha20Poly1305,
+}
+
+impl ChallengeKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        let digest = Sha256::new().chain_update(TOKEN_DOMAIN).chain_update(key).finalize();
+        let mut id = [0u8; KEY_ID_LEN];
+        id.copy_from_slice(&digest[..KEY_ID_LEN]);
+        ChallengeKey {
+            id,
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        }
+    }
+
+    pub fn generate() -> Self {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        ChallengeKey::new(key)
+    }
+
+    pub fn from_hex(value: &str) -> Result<Self, TokenError> {
+        let key: [u8; 32] = hex::decode(value.trim())
+            .map_err(|e| TokenError::Malformed(format!("challenge key is not hex: {}", e)))?
+            .try_into()
+            .map_err(|_| TokenError::Malformed("challenge key must be 32 bytes".to_string()))?;
+        Ok(ChallengeKey::new(key))
+    }
+
+    pub fn id(&self) -> String {
+        hex::encode(self.id)
+    }
+}
+
+// Never print key material
+impl fmt::Debug for ChallengeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ChallengeKey({})", self.id())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Malformed(String),
+    UnknownKey,
+    Forged,
+    Spent,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Malformed(message) => write!(f, "malformed challenge token: {}", message),
+            TokenError::UnknownKey => write!(f, "challenge token was sealed under an unknown or retired key"),
+            TokenError::Forged => write!(f, "challenge token failed authentication"),
+            TokenError::Spent => write!(f, "challenge token has already been answered"),
+        }
+    }
+}
+
+impl Error for TokenError {}
+
+// What a token carries, sealed
+#[derive(Serialize, Deserialize)]
+struct TokenPayload {
+    user: String,
+    r1: String,
+    r2: String,
+    c: String,
+    expires_at_ms: u64,
+}
+
+struct TokenKeys {
+    current: ChallengeKey,
+    // The key rotated out, accepted until the deadline
+    previous: Option<(ChallengeKey, SystemTime)>,
+}
+
+// Seals pending challenges into the auth_id itself so that any replica holding
+// the key can finish a login without shared state. Tokens are encrypted and
+// authenticated with XChaCha20-Poly1305 under a random 192-bit nonce:
+//   base64url(version || key id || nonce || ciphertext)
+//
+// Without shared state a token cannot be made single use across replicas; each
+// replica remembers the tokens it has redeemed until they expire, so a replay
+// is only possible against a different replica within the challenge TTL.
+pub struct ChallengeTokens {
+    keys: RwLock<TokenKeys>,
+    spent: Mutex<HashMap<[u8; NONCE_LEN], SystemTime>>,
+}
+
+impl ChallengeTokens {
+    pub fn new(current: ChallengeKey) -> Self {
+        ChallengeTokens {
+            keys: RwLock::new(TokenKeys { current, previous: None }),
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Keep accepting tokens sealed under `previous` for `grace`
+    pub fn with_previous(self, previous: ChallengeKey, grace: Duration) -> Self {
+        self.keys.write().unwrap().previous = Some((previous, SystemTime::now() + grace));
+        self
+    }
+
+    // One hex-encoded key per line: the current key first, then optionally the
+    // previous one, which keeps opening its tokens for `grace` after loading
+    pub fn load<P: AsRef<Path>>(path: P, grace: Duration) -> Result<Self, Box<dyn Error>> {
+        let (current, previous) = read_keys(path.as_ref())?;
+        Ok(match previous {
+            Some(previous) => ChallengeTokens::new(current).with_previous(previous, grace),
+            None => ChallengeTokens::new(current),
+        })
+    }
+
+    // Re-read the key file and rotate to its first key if that has changed,
+    // returning whether it did. Meant to run when the operator signals a new key.
+    pub fn reload<P: AsRef<Path>>(&self, path: P, grace: Duration) -> Result<bool, Box<dyn Error>> {
+        let (current, _) = read_keys(path.as_ref())?;
+        if current.id == self.keys.read().unwrap().current.id {
+            return Ok(false);
+        }
+        self.rotate(current, grace);
+        Ok(true)
+    }
+
+    // Seal new tokens under `key`; tokens under the old key stay valid for `grace`
+    pub fn rotate(&self, key: ChallengeKey, grace: Duration) {
+        let mut keys = self.keys.write().unwrap();
+        let retired = std::mem::replace(&mut keys.current, key);
+        keys.previous = Some((retired, SystemTime::now() + grace));
+    }
+
+    pub fn current_key_id(&self) -> String {
+        self.keys.read().unwrap().current.id()
+    }
+
+    pub fn seal(&self, challenge: &StoredChallenge) -> String {
+        let payload = TokenPayload {
+            user: challenge.user.clone(),
+            r1: challenge.r1.clone(),
+            r2: challenge.r2.clone(),
+            c: challenge.c.clone(),
+            expires_at_ms: challenge.expires_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+        };
+        let plaintext = serde_json::to_vec(&payload).expect("token payload serialises");
+
+        let keys = self.keys.read().unwrap();
+        let mut header = [0u8; HEADER_LEN];
+        header[0] = TOKEN_VERSION;
+        header[1..].copy_from_slice(&keys.current.id);
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = keys
+            .current
+            .cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &associated_data(&header) })
+            .expect("XChaCha20-Poly1305 encryption does not fail");
+
+        let mut token = Vec::with_capacity(HEADER_LEN + NONCE_LEN + ciphertext.len());
+        token.extend_from_slice(&header);
+        token.extend_from_slice(&nonce);
+        token.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(token)
+    }
+
+    // Check and decrypt a token; expiry is left to the caller
+    pub fn open(&self, token: &str) -> Result<StoredChallenge, TokenError> {
+        self.decrypt(token).map(|(_, challenge)| challenge)
+    }
+
+    // open, then refuse a token this replica has redeemed before
+    pub fn redeem(&self, token: &str) -> Result<StoredChallenge, TokenError> {
+        let (nonce, challenge) = self.decrypt(token)?;
+        let mut spent = self.spent.lock().unwrap();
+        if spent.contains_key(&nonce) {
+            return Err(TokenError::Spent);
+        }
+        spent.insert(nonce, challenge.expires_at);
+        Ok(challenge)
+    }
+
+    // Forget redeemed tokens that have expired anyway, returning how many were dropped
+    pub fn purge_spent(&self, now: SystemTime) -> usize {
+        let mut spent = self.spent.lock().unwrap();
+        let before = spent.len();
+        spent.retain(|_, expires_at| *expires_at > now);
+        before - spent.len()
+    }
+
+    fn decrypt(&self, token: &str) -> Result<([u8; NONCE_LEN], StoredChallenge), TokenError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token)
+            .map_err(|e| TokenError::Malformed(e.to_string()))?;
+        if bytes.len() < HEADER_LEN + NONCE_LEN {
+            return Err(TokenError::Malformed("token is too short".to_string()));
+        }
+        if bytes[0] != TOKEN_VERSION {
+            return Err(TokenError::Malformed(format!("unsupported token version {}", bytes[0])));
+        }
+        let (header, rest) = bytes.split_at(HEADER_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let keys = self.keys.read().unwrap();
+        let key = if header[1..] == keys.current.id {
+            &keys.current
+        } else {
+            match &keys.previous {
+                Some((previous, deadline)) if header[1..] == previous.id => {
+                    if SystemTime::now() > *deadline {
+                        return Err(TokenError::UnknownKey);
+                    }
+                    previous
+                }
+                _ => return Err(TokenError::UnknownKey),
+            }
+        };
+
+        let plaintext = key
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: &associated_data(header) })
+            .map_err(|_| TokenError::Forged)?;
+        let payload: TokenPayload =
+            serde_json::from_slice(&plaintext).map_err(|e| TokenError::Malformed(e.to_string()))?;
+
+        let mut spent_key = [0u8; NONCE_LEN];
+        spent_key.copy_from_slice(nonce);
+        Ok((
+            spent_key,
+            StoredChallenge {
+                user: payload.user,
+                r1: payload.r1,
+                r2: payload.r2,
+                c: payload.c,
+                expires_at: UNIX_EPOCH + Duration::from_millis(payload.expires_at_ms),
+            },
+        ))
+    }
+}
+
+fn associated_data(header: &[u8]) -> Vec<u8> {
+    let mut aad = TOKEN_DOMAIN.to_vec();
+    aad.extend_from_slice(header);
+    aad
+}
+
+// The current and, if listed, previous key in a challenge key file
+fn read_keys(path: &Path) -> Result<(ChallengeKey, Option<ChallengeKey>), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let mut keys = contents.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    let current = ChallengeKey::from_hex(keys.next().ok_or("challenge key file is empty")?)?;
+    let previous = keys.next().map(ChallengeKey::from_hex).transpose()?;
+    if keys.next().is_some() {
+        return Err("challenge key file holds more than a current and a previous key".into());
+    }
+    Ok((current, previous))
+}
@@ -0,0 +1,449 @@
+use pendersen_core::{pem, ParameterSource, DEFAULT_PARAMETERS_PATH};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio_rustls::{
+    rustls::{AllowAnyAuthenticatedClient, NoClientAuth, ProtocolVersion, RootCertStore, ServerConfig as RustlsConfig},
+    TlsAcceptor,
+};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::auth_service::{DEFAULT_CHALLENGE_TTL, DEFAULT_LOGIN_WINDOW};
+use crate::lockout::LockoutPolicy;
+use crate::rate_limit::{
+    RateLimit, RateLimitPolicy, DEFAULT_PROOF_RATE_LIMIT, DEFAULT_RATE_LIMIT, MIN_PER_SECOND, PROOF_METHODS, RPC_METHODS,
+};
+use crate::session_registry::DEFAULT_SESSION_TTL;
+use crate::session_store::SessionStoreConfig;
+use crate::user_store::UserStoreConfig;
+
+pub const DEFAULT_LISTEN_ADDR: &str = "[::1]:50051";
+
+// Environment variable naming the config file, like --config
+pub const CONFIG_VAR: &str = "PENDERSEN_CONFIG";
+
+// Settings that can be overridden one at a time: command-line flag,
+// environment variable and the dotted key in the config file
+const SETTINGS: &[(&str, &str, &str)] = &[
+    ("--listen", "PENDERSEN_LISTEN", "listen"),
+    ("--parameters", "PENDERSEN_PARAMETERS", "parameters"),
+    ("--user-store", "PENDERSEN_USER_STORE", "user_store"),
+    ("--session-store", "PENDERSEN_SESSION_STORE", "session_store"),
+    ("--challenge-keys", "PENDERSEN_CHALLENGE_KEYS", "challenge_keys"),
+    ("--server-key", "PENDERSEN_SERVER_KEY", "server_key"),
+    ("--log-level", "PENDERSEN_LOG_LEVEL", "log_level"),
+    ("--challenge-ttl", "PENDERSEN_CHALLENGE_TTL", "ttl.challenge_secs"),
+    ("--session-ttl", "PENDERSEN_SESSION_TTL", "ttl.session_secs"),
+    ("--login-window", "PENDERSEN_LOGIN_WINDOW", "ttl.login_window_secs"),
+    ("--lockout-threshold", "PENDERSEN_LOCKOUT_THRESHOLD", "lockout.threshold"),
+    ("--lockout-base-delay", "PENDERSEN_LOCKOUT_BASE_DELAY", "lockout.base_delay_secs"),
+    ("--lockout-max-delay", "PENDERSEN_LOCKOUT_MAX_DELAY", "lockout.max_delay_secs"),
+    ("--rate-limit", "PENDERSEN_RATE_LIMIT", "rate_limit.enabled"),
+    ("--rate-limit-burst", "PENDERSEN_RATE_LIMIT_BURST", "rate_limit.burst"),
+    ("--rate-limit-per-second", "PENDERSEN_RATE_LIMIT_PER_SECOND", "rate_limit.per_second"),
+    ("--rate-limit-allow", "PENDERSEN_RATE_LIMIT_ALLOW", "rate_limit.allowlist"),
+    ("--tls-cert", "PENDERSEN_TLS_CERT", "tls.cert"),
+    ("--tls-key", "PENDERSEN_TLS_KEY", "tls.key"),
+    ("--tls-client-ca", "PENDERSEN_TLS_CLIENT_CA", "tls.client_ca"),
+];
+
+pub const USAGE: &str = "\
+usage: pendersen_server [--config PATH] [--check-config] [OPTIONS] [PARAMETERS [USER_STORE [SESSION_STORE]]]
+
+  --config PATH               TOML config file (or PENDERSEN_CONFIG)
+  --check-config              validate the configuration, print it and exit
+  --listen ADDR               address to serve on, default [::1]:50051
+  --parameters SOURCE         built-in set name or parameter file
+  --user-store SPEC           memory, json:<path> or sqlite:<path>
+  --session-store SPEC        memory or sqlite:<path>
+  --challenge-keys PATH       seal challenges into the auth_id with these keys
+  --server-key PATH           prove logins with the long-term key seeded from this file
+  --challenge-ttl SECS        --session-ttl SECS        --login-window SECS
+  --lockout-threshold N       --lockout-base-delay SECS --lockout-max-delay SECS
+  --rate-limit on|off         --rate-limit-burst N      --rate-limit-per-second RATE
+  --rate-limit-allow ADDR,... addresses that are never rate limited
+  --tls-cert PATH             --tls-key PATH            --tls-client-ca PATH
+  --log-level LEVEL           off, error, warn, info, debug or trace
+
+Every option can also be set with the PENDERSEN_ environment variable of the same
+name, e.g. PENDERSEN_SESSION_TTL. Flags win over the environment, which wins over
+the config file.";
+
+// Everything the server binary needs to start. Values are kept in the form
+// they are written in the config file so that the effective configuration
+// can be printed back as TOML; the accessors parse them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen: String,
+    // Built-in set name or parameter file path
+    pub parameters: String,
+    pub user_store: String,
+    pub session_store: String,
+    pub challenge_keys: Option<PathBuf>,
+    // Hex seed of the long-term key the server proves its identity with
+    pub server_key: Option<PathBuf>,
+    pub log_level: String,
+    pub ttl: TtlConfig,
+    pub lockout: LockoutConfig,
+    pub rate_limit: RateLimitConfig,
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TtlConfig {
+    pub challenge_secs: u64,
+    pub session_secs: u64,
+    pub login_window_secs: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockoutConfig {
+    // Zero disables lockout
+    pub threshold: u32,
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+}
+
+// The limit for methods not listed under `methods`, plus per-method overrides.
+// Methods given in the file are added to the built-in proof method limits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub burst: u32,
+    pub per_second: f64,
+    pub allowlist: Vec<IpAddr>,
+    #[serde(deserialize_with = "merge_method_limits")]
+    pub methods: BTreeMap<String, RateLimit>,
+}
+
+// PEM files for the gRPC listener; with client_ca set, clients must present a
+// certificate signed by it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: DEFAULT_LISTEN_ADDR.to_string(),
+            parameters: DEFAULT_PARAMETERS_PATH.to_string(),
+            user_store: "memory".to_string(),
+            session_store: "memory".to_string(),
+            challenge_keys: None,
+            server_key: None,
+            log_level: "info".to_string(),
+            ttl: TtlConfig::default(),
+            lockout: LockoutConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            tls: None,
+        }
+    }
+}
+
+impl Default for TtlConfig {
+    fn default() -> Self {
+        TtlConfig {
+            challenge_secs: DEFAULT_CHALLENGE_TTL.as_secs(),
+            session_secs: DEFAULT_SESSION_TTL.as_secs(),
+            login_window_secs: DEFAULT_LOGIN_WINDOW.as_secs(),
+        }
+    }
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        let policy = LockoutPolicy::default();
+        LockoutConfig {
+            threshold: policy.threshold,
+            base_delay_secs: policy.base_delay.as_secs(),
+            max_delay_secs: policy.max_delay.as_secs(),
+        }
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            enabled: true,
+            burst: DEFAULT_RATE_LIMIT.burst,
+            per_second: DEFAULT_RATE_LIMIT.per_second,
+            allowlist: Vec::new(),
+            methods: default_method_limits(),
+        }
+    }
+}
+
+fn default_method_limits() -> BTreeMap<String, RateLimit> {
+    PROOF_METHODS
+        .iter()
+        .map(|method| (method.to_string(), DEFAULT_PROOF_RATE_LIMIT))
+        .collect()
+}
+
+fn merge_method_limits<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, RateLimit>, D::Error> {
+    let mut methods = default_method_limits();
+    methods.extend(BTreeMap::<String, RateLimit>::deserialize(deserializer)?);
+    Ok(methods)
+}
+
+impl TlsConfig {
+    // Read the PEM files into a TLS acceptor; requiring client_ca turns on mutual TLS.
+    // Only TLS 1.3 is offered, since the channel binding relies on its exporter.
+    pub fn load(&self) -> Result<TlsAcceptor, Box<dyn Error>> {
+        let certs = pem::certificates(&self.cert)?;
+        let key = pem::private_key(&self.key)?;
+        let mut config = match &self.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                pem::add_roots(&mut roots, path)?;
+                RustlsConfig::new(AllowAnyAuthenticatedClient::new(roots))
+            }
+            None => RustlsConfig::new(NoClientAuth::new()),
+        };
+        config.set_single_cert(certs, key)?;
+        config.set_protocols(&[b"h2".to_vec()]);
+        config.versions = vec![ProtocolVersion::TLSv1_3];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+// What the command line asked the server binary to do
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invocation {
+    pub config: ServerConfig,
+    pub check_config: bool,
+    pub help: bool,
+}
+
+impl Invocation {
+    // Layer the config file, then the environment, then the flags in `args`.
+    // `env` looks up environment variables so tests need not touch the real ones.
+    pub fn parse<F>(args: Vec<String>, env: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config_path = env(CONFIG_VAR).map(PathBuf::from);
+        let mut check_config = false;
+        let mut help = false;
+        let mut flags = Vec::new();
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--config" => config_path = Some(PathBuf::from(value()?)),
+                "--check-config" => check_config = true,
+                "--help" | "-h" => help = true,
+                flag if flag.starts_with("--") => match SETTINGS.iter().find(|(name, _, _)| *name == flag) {
+                    Some((_, _, key)) => flags.push((*key, value()?)),
+                    None => return Err(format!("unknown argument {:?}", flag).into()),
+                },
+                _ => positional.push(arg),
+            }
+        }
+        if positional.len() > 3 {
+            return Err(format!("unexpected argument {:?}", positional[3]).into());
+        }
+
+        let mut config = match &config_path {
+            Some(path) => ServerConfig::load(path)?,
+            None => ServerConfig::default(),
+        };
+        for (_, var, key) in SETTINGS {
+            if let Some(value) = env(var) {
+                config.set(key, &value).map_err(|e| format!("{}: {}", var, e))?;
+            }
+        }
+        // PARAMETERS USER_STORE SESSION_STORE, as before there were flags
+        for (key, value) in ["parameters", "user_store", "session_store"].into_iter().zip(positional) {
+            config.set(key, &value)?;
+        }
+        for (key, value) in flags {
+            config.set(key, &value)?;
+        }
+        config.validate()?;
+
+        Ok(Invocation { config, check_config, help })
+    }
+}
+
+impl ServerConfig {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Self::from_toml(&contents).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("server configuration serializes to TOML")
+    }
+
+    // Override one setting by its dotted key, parsing `value` as a flag would
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let secs = |value: &str| value.parse::<u64>().map_err(|_| format!("invalid {} {:?}: expected whole seconds", key, value));
+        let number = |value: &str| value.parse::<u32>().map_err(|_| format!("invalid {} {:?}", key, value));
+        match key {
+            "listen" => self.listen = value.to_string(),
+            "parameters" => self.parameters = value.to_string(),
+            "user_store" => self.user_store = value.to_string(),
+            "session_store" => self.session_store = value.to_string(),
+            "challenge_keys" => self.challenge_keys = Some(PathBuf::from(value)),
+            "server_key" => self.server_key = Some(PathBuf::from(value)),
+            "log_level" => self.log_level = value.to_string(),
+            "ttl.challenge_secs" => self.ttl.challenge_secs = secs(value)?,
+            "ttl.session_secs" => self.ttl.session_secs = secs(value)?,
+            "ttl.login_window_secs" => self.ttl.login_window_secs = secs(value)?,
+            "lockout.threshold" => self.lockout.threshold = number(value)?,
+            "lockout.base_delay_secs" => self.lockout.base_delay_secs = secs(value)?,
+            "lockout.max_delay_secs" => self.lockout.max_delay_secs = secs(value)?,
+            "rate_limit.enabled" => {
+                self.rate_limit.enabled = match value {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => return Err(format!("invalid {} {:?}: expected on or off", key, value)),
+                }
+            }
+            "rate_limit.burst" => self.rate_limit.burst = number(value)?,
+            "rate_limit.per_second" => {
+                self.rate_limit.per_second = value.parse().map_err(|_| format!("invalid {} {:?}", key, value))?
+            }
+            "rate_limit.allowlist" => {
+                self.rate_limit.allowlist = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|addr| !addr.is_empty())
+                    .map(|addr| addr.parse().map_err(|_| format!("invalid address {:?} in {}", addr, key)))
+                    .collect::<Result<_, _>>()?
+            }
+            "tls.cert" => self.tls.get_or_insert_with(TlsConfig::default).cert = PathBuf::from(value),
+            "tls.key" => self.tls.get_or_insert_with(TlsConfig::default).key = PathBuf::from(value),
+            "tls.client_ca" => self.tls.get_or_insert_with(TlsConfig::default).client_ca = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown setting {:?}", key)),
+        }
+        Ok(())
+    }
+
+    // Check every value parses and the combination makes sense, without opening anything
+    pub fn validate(&self) -> Result<(), String> {
+        self.listen_addr()?;
+        self.user_store_config()?;
+        self.session_store_config()?;
+        self.log_level_filter()?;
+        if self.ttl.challenge_secs == 0 || self.ttl.session_secs == 0 {
+            return Err("ttl.challenge_secs and ttl.session_secs must be positive".to_string());
+        }
+        if self.lockout.threshold > 0 && self.lockout.base_delay_secs > self.lockout.max_delay_secs {
+            return Err("lockout.base_delay_secs must not exceed lockout.max_delay_secs".to_string());
+        }
+        let default = self.rate_limit_default();
+        let limits = std::iter::once(("rate_limit", &default))
+            .chain(self.rate_limit.methods.iter().map(|(method, limit)| (method.as_str(), limit)));
+        for (name, limit) in limits {
+            if limit.burst == 0 || !(limit.per_second.is_finite() && limit.per_second >= MIN_PER_SECOND) {
+                return Err(format!("{}: burst must be positive and per_second at least {}", name, MIN_PER_SECOND));
+            }
+        }
+        if let Some(method) = self.rate_limit.methods.keys().find(|method| !RPC_METHODS.contains(&method.as_str())) {
+            return Err(format!("rate_limit.methods: unknown RPC {:?}", method));
+        }
+        if let Some(tls) = &self.tls {
+            if tls.cert.as_os_str().is_empty() || tls.key.as_os_str().is_empty() {
+                return Err("tls needs both cert and key".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn listen_addr(&self) -> Result<SocketAddr, String> {
+        self.listen.parse().map_err(|_| format!("invalid listen address {:?}", self.listen))
+    }
+
+    pub fn parameter_source(&self) -> ParameterSource {
+        ParameterSource::parse(&self.parameters)
+    }
+
+    pub fn user_store_config(&self) -> Result<UserStoreConfig, String> {
+        UserStoreConfig::parse(&self.user_store)
+    }
+
+    pub fn session_store_config(&self) -> Result<SessionStoreConfig, String> {
+        SessionStoreConfig::parse(&self.session_store)
+    }
+
+    pub fn log_level_filter(&self) -> Result<log::LevelFilter, String> {
+        self.log_level.parse().map_err(|_| format!("invalid log level {:?}", self.log_level))
+    }
+
+    // The 32-byte seed in the server key file, hex encoded as `openssl rand -hex 32` writes it
+    pub fn server_key_seed(&self) -> Result<Option<[u8; 32]>, String> {
+        let path = match &self.server_key {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let contents = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let seed = hex::decode(contents.trim()).map_err(|e| format!("{}: server key is not hex: {}", path.display(), e))?;
+        seed.try_into()
+            .map(Some)
+            .map_err(|_| format!("{}: server key must be 32 bytes", path.display()))
+    }
+
+    pub fn challenge_ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl.challenge_secs)
+    }
+
+    pub fn session_ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl.session_secs)
+    }
+
+    pub fn login_window(&self) -> Duration {
+        Duration::from_secs(self.ttl.login_window_secs)
+    }
+
+    pub fn lockout_policy(&self) -> LockoutPolicy {
+        LockoutPolicy {
+            threshold: self.lockout.threshold,
+            base_delay: Duration::from_secs(self.lockout.base_delay_secs),
+            max_delay: Duration::from_secs(self.lockout.max_delay_secs),
+        }
+    }
+
+    // Lets everything through when rate limiting is switched off
+    pub fn rate_limit_policy(&self) -> RateLimitPolicy {
+        if !self.rate_limit.enabled {
+            return RateLimitPolicy::unlimited();
+        }
+        let policy = RateLimitPolicy::unlimited().with_default(Some(self.rate_limit_default()));
+        let policy = self
+            .rate_limit
+            .methods
+            .iter()
+            .fold(policy, |policy, (method, limit)| policy.with_method(method, *limit));
+        self.rate_limit.allowlist.iter().fold(policy, |policy, addr| policy.allow(*addr))
+    }
+
+    fn rate_limit_default(&self) -> RateLimit {
+        RateLimit {
+            burst: self.rate_limit.burst,
+            per_second: self.rate_limit.per_second,
+        }
+    }
+}
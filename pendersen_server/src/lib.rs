@@ -1,8 +1,49 @@
 // src/lib.rs
 
+// Handlers and their helpers return tonic::Status, which is large by design
+#![allow(clippy::result_large_err)]
+
 pub mod pb {
     tonic::include_proto!("zkp_auth"); // Adjust "zkp_auth" to match your .proto package name
 }
 
 pub mod auth_service;
+pub mod challenge_token;
+pub mod config;
+pub mod lockout;
+pub mod rate_limit;
+pub mod session_registry;
+pub mod session_store;
+pub mod tls;
+pub mod user_store;
 pub use auth_service::AuthService;
+pub use challenge_token::{ChallengeKey, ChallengeTokens, TokenError};
+pub use config::{Invocation, ServerConfig};
+pub use lockout::{AttemptTracker, LockoutPolicy};
+pub use rate_limit::{RateLimit, RateLimitLayer, RateLimitPolicy, RateLimiter};
+pub use session_registry::{Session, SessionRegistry};
+pub use session_store::{MemorySessionStore, SessionStore, SessionStoreConfig, SqliteSessionStore, StoredChallenge};
+pub use tls::{tls_incoming, ChannelBinding, ConnectionInfo, TlsConnection};
+pub use user_store::{JsonFileUserStore, MemoryUserStore, SqliteUserStore, StoreError, StoredUser, UserStore, UserStoreConfig};
+
+impl From<pb::KdfParameters> for pendersen_core::KdfParameters {
+    fn from(kdf: pb::KdfParameters) -> Self {
+        pendersen_core::KdfParameters {
+            salt: kdf.salt,
+            memory_kib: kdf.memory_kib,
+            iterations: kdf.iterations,
+            parallelism: kdf.parallelism,
+        }
+    }
+}
+
+impl From<pendersen_core::KdfParameters> for pb::KdfParameters {
+    fn from(kdf: pendersen_core::KdfParameters) -> Self {
+        pb::KdfParameters {
+            salt: kdf.salt,
+            memory_kib: kdf.memory_kib,
+            iterations: kdf.iterations,
+            parallelism: kdf.parallelism,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+// Failed proofs a user may submit before being locked out
+pub const DEFAULT_LOCKOUT_THRESHOLD: u32 = 5;
+
+// Length of the first lockout; each further failure doubles it
+pub const DEFAULT_LOCKOUT_BASE: Duration = Duration::from_secs(30);
+
+// Longest lockout, and how long after the last failure the count is forgotten
+pub const DEFAULT_LOCKOUT_MAX: Duration = Duration::from_secs(15 * 60);
+
+// Response metadata key carrying the number of seconds until the lockout lifts
+pub const RETRY_AFTER_METADATA: &str = "retry-after";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub threshold: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        LockoutPolicy {
+            threshold: DEFAULT_LOCKOUT_THRESHOLD,
+            base_delay: DEFAULT_LOCKOUT_BASE,
+            max_delay: DEFAULT_LOCKOUT_MAX,
+        }
+    }
+}
+
+impl LockoutPolicy {
+    // How long to lock a user out after `failures` consecutive failed proofs:
+    // nothing below the threshold, then base_delay * 2^(failures - threshold)
+    pub fn delay(&self, failures: u32) -> Option<Duration> {
+        if self.threshold == 0 || failures < self.threshold {
+            return None;
+        }
+        let doublings = (failures - self.threshold).min(31);
+        Some(self.base_delay.saturating_mul(1 << doublings).min(self.max_delay))
+    }
+}
+
+struct Attempts {
+    failures: u32,
+    last_failure: SystemTime,
+    locked_until: Option<SystemTime>,
+}
+
+// Consecutive failed proofs per user, kept in memory by each server process.
+// Replicas count separately, so behind a load balancer a user gets up to
+// `threshold` guesses per replica before being locked out.
+pub struct AttemptTracker {
+    policy: LockoutPolicy,
+    users: Mutex<HashMap<String, Attempts>>,
+}
+
+impl AttemptTracker {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        AttemptTracker {
+            policy,
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> LockoutPolicy {
+        self.policy
+    }
+
+    // Err with the time left if the user is currently locked out
+    pub fn check(&self, user: &str, now: SystemTime) -> Result<(), Duration> {
+        let users = self.users.lock().unwrap();
+        match users.get(user).and_then(|attempts| attempts.locked_until) {
+            Some(until) if until > now => Err(until.duration_since(now).unwrap_or_default()),
+            _ => Ok(()),
+        }
+    }
+
+    // Check the user is not locked out and count the attempt as failed under one
+    // lock, so concurrent guesses cannot all pass the check before any of them is
+    // recorded. A proof that verifies clears the count with record_success.
+    pub fn begin_attempt(&self, user: &str, now: SystemTime) -> Result<(), Duration> {
+        let mut users = self.users.lock().unwrap();
+        if let Some(until) = users.get(user).and_then(|attempts| attempts.locked_until) {
+            if until > now {
+                return Err(until.duration_since(now).unwrap_or_default());
+            }
+        }
+        self.count_failure(&mut users, user, now);
+        Ok(())
+    }
+
+    // Count a failed proof, returning the lockout it triggered, if any
+    pub fn record_failure(&self, user: &str, now: SystemTime) -> Option<Duration> {
+        self.count_failure(&mut self.users.lock().unwrap(), user, now)
+    }
+
+    fn count_failure(&self, users: &mut HashMap<String, Attempts>, user: &str, now: SystemTime) -> Option<Duration> {
+        let attempts = users.entry(user.to_string()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if self.is_stale(attempts, now) {
+            attempts.failures = 0;
+        }
+        attempts.failures = attempts.failures.saturating_add(1);
+        attempts.last_failure = now;
+        let delay = self.policy.delay(attempts.failures);
+        attempts.locked_until = delay.map(|delay| now + delay);
+        delay
+    }
+
+    pub fn record_success(&self, user: &str) {
+        self.users.lock().unwrap().remove(user);
+    }
+
+    pub fn failures(&self, user: &str) -> u32 {
+        self.users.lock().unwrap().get(user).map_or(0, |attempts| attempts.failures)
+    }
+
+    // Forget users who are no longer locked out and have not failed for max_delay
+    pub fn purge(&self, now: SystemTime) -> usize {
+        let mut users = self.users.lock().unwrap();
+        let before = users.len();
+        users.retain(|_, attempts| !self.is_stale(attempts, now));
+        before - users.len()
+    }
+
+    fn is_stale(&self, attempts: &Attempts, now: SystemTime) -> bool {
+        let unlocked = attempts.locked_until.is_none_or(|until| until <= now);
+        let quiet = now.duration_since(attempts.last_failure).unwrap_or_default() >= self.policy.max_delay;
+        unlocked && quiet
+    }
+}
+
+impl Default for AttemptTracker {
+    fn default() -> Self {
+        AttemptTracker::new(LockoutPolicy::default())
+    }
+}
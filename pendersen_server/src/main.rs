@@ -1,26 +1,142 @@
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 use tonic::transport::Server;
 use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-mod pb {
-    tonic::include_proto!("zkp_auth");
-}
+use pendersen_core::{Backend, Group, ServerKey};
+use pendersen_server::{tls_incoming, AuthService, ChallengeTokens, Invocation, RateLimitLayer, ServerConfig};
+use pendersen_server::pb::auth_server::AuthServer;
 
-mod auth_service;
-use auth_service::AuthService;
-use pb::auth_server::AuthServer;
+// How often expired challenges, sessions and idle rate limit buckets are evicted
+const REAPER_INTERVAL: Duration = Duration::from_secs(30);
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let (p, q, g, h) = AuthService::load_parameters()?;
-    let addr = "[::1]:50051".parse()?;
-    let auth_service = AuthService::new(g, h, p, q);
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("pendersen_server: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let invocation = Invocation::parse(std::env::args().skip(1).collect(), |var| std::env::var(var).ok())?;
+    if invocation.help {
+        println!("{}", pendersen_server::config::USAGE);
+        return Ok(());
+    }
+    let config = invocation.config;
+
+    // Resolve everything that can fail before printing the configuration as valid
+    let source = config.parameter_source();
+    let backend = source.load()?;
+    let challenge_tokens = match &config.challenge_keys {
+        // A previous key in the file opens its tokens for one challenge TTL
+        Some(path) => Some(Arc::new(ChallengeTokens::load(path, config.challenge_ttl())?)),
+        None => None,
+    };
+    let server_key_seed = config.server_key_seed()?;
+    // Building the acceptor parses the certificate and key
+    let acceptor = match &config.tls {
+        Some(tls) => Some(tls.load()?),
+        None => None,
+    };
+    if invocation.check_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+
+    env_logger::Builder::new().filter_level(config.log_level_filter()?).init();
+    log::info!("Using {} group from {}", backend.name(), source);
+    if let (Some(tokens), Some(path)) = (&challenge_tokens, &config.challenge_keys) {
+        log::info!("Sealing challenges under key {}", tokens.current_key_id());
+        spawn_key_reloader(Arc::clone(tokens), path.clone(), config.challenge_ttl())?;
+    }
+
+    match backend {
+        Backend::ModP(group) => serve(acceptor, group, &config, challenge_tokens, server_key_seed).await,
+        Backend::Ristretto255(group) => serve(acceptor, group, &config, challenge_tokens, server_key_seed).await,
+    }
+}
+
+// Rotate to the first key in the challenge key file whenever the process gets
+// SIGHUP; tokens sealed under the replaced key keep opening for `grace`
+#[cfg(unix)]
+fn spawn_key_reloader(tokens: Arc<ChallengeTokens>, path: PathBuf, grace: Duration) -> Result<(), Box<dyn Error>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match tokens.reload(&path, grace) {
+                Ok(true) => log::info!("Sealing challenges under key {}", tokens.current_key_id()),
+                Ok(false) => log::info!("Challenge key in {} is unchanged", path.display()),
+                Err(e) => log::error!("Failed to reload challenge keys from {}: {}", path.display(), e),
+            }
+        }
+    });
+    Ok(())
+}
+
+// No SIGHUP here; restart the server to pick up a new key
+#[cfg(not(unix))]
+fn spawn_key_reloader(_: Arc<ChallengeTokens>, _: PathBuf, _: Duration) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+async fn serve<G: Group>(
+    acceptor: Option<TlsAcceptor>,
+    group: G,
+    config: &ServerConfig,
+    challenge_tokens: Option<Arc<ChallengeTokens>>,
+    server_key_seed: Option<[u8; 32]>,
+) -> Result<(), Box<dyn Error>> {
+    let addr = config.listen_addr()?;
+    let user_store = config.user_store_config()?;
+    let session_store = config.session_store_config()?;
+    log::info!("Using {}", user_store);
+    log::info!("Using {}", session_store);
+
+    // The key depends on the group, so it is derived only now
+    let server_key = server_key_seed.map(|seed| ServerKey::from_seed(&group, &seed));
+    if let Some(server_key) = &server_key {
+        log::info!("Proving logins with server key {}", group.encode_element(server_key.public()));
+    }
+
+    let mut auth_service = AuthService::new(group)
+        .with_user_store(user_store.open()?)
+        .with_session_store(session_store.open()?)
+        .with_challenge_ttl(config.challenge_ttl())
+        .with_session_ttl(config.session_ttl())
+        .with_login_window(config.login_window())
+        .with_lockout_policy(config.lockout_policy());
+    if let Some(tokens) = challenge_tokens {
+        auth_service = auth_service.with_challenge_tokens(tokens);
+    }
+    if let Some(server_key) = server_key {
+        auth_service = auth_service.with_server_key(server_key);
+    }
+    auth_service.spawn_challenge_reaper(REAPER_INTERVAL);
+
+    let rate_limit = RateLimitLayer::new(config.rate_limit_policy());
+    rate_limit.spawn_reaper(REAPER_INTERVAL);
 
-    println!("Server listening on {}", addr);
+    match &config.tls {
+        Some(tls) if tls.client_ca.is_some() => log::info!("Server listening on {} with mutual TLS", addr),
+        Some(_) => log::info!("Server listening on {} with TLS", addr),
+        None => log::info!("Server listening on {}", addr),
+    }
 
-    Server::builder()
-        .add_service(AuthServer::new(auth_service))
-        .serve(addr)
-        .await?;
+    let router = Server::builder()
+        .layer(rate_limit)
+        .add_service(AuthServer::new(auth_service));
+    match acceptor {
+        // Handshakes run here rather than in tonic so each connection's exporter value is known
+        Some(acceptor) => router.serve_with_incoming(tls_incoming(TcpListener::bind(addr).await?, acceptor)).await?,
+        None => router.serve(addr).await?,
+    }
 
     Ok(())
 }
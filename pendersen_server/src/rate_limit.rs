@@ -0,0 +1,262 @@
+use tonic::{body::BoxBody, codegen::http, metadata::MetadataValue, transport::server::TcpConnectInfo, Status};
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::IpAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use crate::lockout::RETRY_AFTER_METADATA;
+use crate::tls::ConnectionInfo;
+
+// Requests a single address may make to an RPC without its own limit
+pub const DEFAULT_RATE_LIMIT: RateLimit = RateLimit { burst: 20, per_second: 10.0 };
+
+// Requests a single address may make to an RPC that verifies a proof
+pub const DEFAULT_PROOF_RATE_LIMIT: RateLimit = RateLimit { burst: 5, per_second: 1.0 };
+
+// RPCs whose handlers exponentiate to check a proof
+pub const PROOF_METHODS: [&str; 4] = ["Register", "VerifyAuthentication", "Login", "ChangeCredentials"];
+
+// Every RPC of the Auth service. Any other method name shares one bucket per
+// address, so clients cannot grow the bucket table by making up paths.
+pub const RPC_METHODS: [&str; 7] = [
+    "Register",
+    "GetKdfParameters",
+    "CreateAuthenticationChallenge",
+    "VerifyAuthentication",
+    "Login",
+    "ValidateSession",
+    "ChangeCredentials",
+];
+
+// The bucket shared by requests for anything but an Auth RPC
+const OTHER_METHOD: &str = "*";
+
+// Slowest refill a limit may have, one token a day; waits for slower ones
+// would not fit in a Duration
+pub const MIN_PER_SECOND: f64 = 1.0 / 86_400.0;
+
+// Token bucket: up to `burst` requests at once, refilled at `per_second`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+// Which limit applies to each RPC, keyed by method name such as "Login",
+// and which addresses are never limited
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitPolicy {
+    pub default: Option<RateLimit>,
+    pub methods: HashMap<String, RateLimit>,
+    pub allowlist: HashSet<IpAddr>,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        RateLimitPolicy {
+            default: Some(DEFAULT_RATE_LIMIT),
+            methods: PROOF_METHODS
+                .iter()
+                .map(|method| (method.to_string(), DEFAULT_PROOF_RATE_LIMIT))
+                .collect(),
+            allowlist: HashSet::new(),
+        }
+    }
+}
+
+impl RateLimitPolicy {
+    // No limits at all, to build up from
+    pub fn unlimited() -> Self {
+        RateLimitPolicy {
+            default: None,
+            methods: HashMap::new(),
+            allowlist: HashSet::new(),
+        }
+    }
+
+    pub fn with_default(mut self, limit: Option<RateLimit>) -> Self {
+        self.default = limit;
+        self
+    }
+
+    pub fn with_method(mut self, method: &str, limit: RateLimit) -> Self {
+        self.methods.insert(method.to_string(), limit);
+        self
+    }
+
+    pub fn allow(mut self, addr: IpAddr) -> Self {
+        self.allowlist.insert(addr.to_canonical());
+        self
+    }
+
+    fn limit(&self, addr: IpAddr, method: &str) -> Option<RateLimit> {
+        if self.allowlist.contains(&addr) {
+            return None;
+        }
+        self.methods.get(method).copied().or(self.default)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// One token bucket per remote address and method
+pub struct RateLimiter {
+    policy: RateLimitPolicy,
+    buckets: Mutex<HashMap<(IpAddr, &'static str), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitPolicy) -> Self {
+        RateLimiter {
+            policy,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn policy(&self) -> &RateLimitPolicy {
+        &self.policy
+    }
+
+    // Take a token for this request, or Err with how long until one is available
+    pub fn check(&self, addr: IpAddr, method: &str, now: Instant) -> Result<(), Duration> {
+        let addr = addr.to_canonical();
+        let method = RPC_METHODS.iter().find(|known| **known == method).copied().unwrap_or(OTHER_METHOD);
+        let limit = match self.policy.limit(addr, method) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry((addr, method)).or_insert(Bucket {
+            tokens: f64::from(limit.burst),
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(f64::from(limit.burst));
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            // Policies built in code skip the config's MIN_PER_SECOND check
+            Err(Duration::try_from_secs_f64((1.0 - bucket.tokens) / limit.per_second).unwrap_or(Duration::MAX))
+        }
+    }
+
+    // Forget buckets that have refilled, since a fresh one behaves the same
+    pub fn purge(&self, now: Instant) -> usize {
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = buckets.len();
+        buckets.retain(|(addr, method), bucket| match self.policy.limit(*addr, method) {
+            Some(limit) => {
+                let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * limit.per_second < f64::from(limit.burst)
+            }
+            None => false,
+        });
+        before - buckets.len()
+    }
+}
+
+// Tower layer that rejects requests over their address's rate limit with
+// RESOURCE_EXHAUSTED before they reach the AuthServer
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(policy: RateLimitPolicy) -> Self {
+        RateLimitLayer {
+            limiter: Arc::new(RateLimiter::new(policy)),
+        }
+    }
+
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+
+    // Drop idle buckets every `interval` on the tokio runtime
+    pub fn spawn_reaper(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let limiter = Arc::clone(&self.limiter);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                limiter.purge(Instant::now());
+            }
+        })
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimitService<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        // Plain TCP connections carry TcpConnectInfo and TLS ones our ConnectionInfo;
+        // connections without a peer address, such as in-process ones, are not limited
+        let addr = request
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|info| info.remote_addr())
+            .or_else(|| request.extensions().get::<ConnectionInfo>().and_then(|info| info.remote_addr))
+            .map(|addr| addr.ip());
+        if let Some(addr) = addr {
+            // gRPC paths look like /zkp_auth.Auth/VerifyAuthentication
+            let method = request.uri().path().rsplit('/').next().unwrap_or_default();
+            if let Err(wait) = self.limiter.check(addr, method, Instant::now()) {
+                log::warn!("Rate limiting {} on {}", addr, method);
+                let response = rate_limited(wait).to_http();
+                return Box::pin(async move { Ok(response) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
+
+// RESOURCE_EXHAUSTED with the whole seconds until the next token in the retry-after metadata
+fn rate_limited(wait: Duration) -> Status {
+    let seconds = wait.as_secs().saturating_add(u64::from(wait.subsec_nanos() > 0));
+    let mut status = Status::resource_exhausted(format!("Rate limit exceeded; retry in {} seconds", seconds));
+    status
+        .metadata_mut()
+        .insert(RETRY_AFTER_METADATA, MetadataValue::from(seconds));
+    status
+}
@@ -0,0 +1,99 @@
+use pendersen_core::SessionKey;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use crate::session_store::{MemorySessionStore, SessionStore};
+use crate::user_store::StoreError;
+
+// How long an issued session token stays valid
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+
+// Domain separators for what the store keeps in place of the token and key
+const SESSION_ID_DOMAIN: &[u8] = b"pendersen-project/session-id/v1";
+const SESSION_KEY_MASK_DOMAIN: &[u8] = b"pendersen-project/session-key-mask/v1";
+
+// A successful login: the user it belongs to, when it stops being valid, and
+// the key agreed with the client if the login carried a key exchange
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub user: String,
+    pub expires_at: SystemTime,
+    pub key: Option<SessionKey>,
+}
+
+impl Session {
+    pub fn expires_at_unix(&self) -> u64 {
+        self.expires_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+}
+
+// Mints session tokens and looks them up. The store only sees a hash of each
+// token, and the session key masked with a pad derived from the token, so
+// reading the store yields neither usable tokens nor keys. Services sharing the
+// store recover a session's key by validating the client's token here.
+pub struct SessionRegistry {
+    store: Arc<dyn SessionStore>,
+    ttl: Duration,
+}
+
+impl SessionRegistry {
+    pub fn new(store: Arc<dyn SessionStore>, ttl: Duration) -> Self {
+        SessionRegistry { store, ttl }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Mint a 256-bit random token for `user`
+    pub fn issue(&self, user: &str, key: Option<SessionKey>) -> Result<(String, Session), StoreError> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+
+        let session = Session {
+            user: user.to_string(),
+            expires_at: SystemTime::now() + self.ttl,
+            key,
+        };
+
+        let stored = Session {
+            key: session.key.as_ref().map(|key| mask(&token, key)),
+            ..session.clone()
+        };
+        self.store.put_session(&session_id(&token), &stored)?;
+        Ok((token, session))
+    }
+
+    // The session behind `token`, if it exists and has not expired
+    pub fn validate(&self, token: &str) -> Result<Option<Session>, StoreError> {
+        Ok(self.store.get_session(&session_id(token))?.map(|session| Session {
+            key: session.key.as_ref().map(|key| mask(token, key)),
+            ..session
+        }))
+    }
+
+    pub fn revoke(&self, token: &str) -> Result<bool, StoreError> {
+        self.store.revoke_session(&session_id(token))
+    }
+}
+
+// What the store files a session under
+fn session_id(token: &str) -> String {
+    hex::encode(Sha256::new().chain_update(SESSION_ID_DOMAIN).chain_update(token).finalize())
+}
+
+// XOR with a pad only the token holder can derive; applying it twice unmasks
+fn mask(token: &str, key: &SessionKey) -> SessionKey {
+    let pad = Sha256::new().chain_update(SESSION_KEY_MASK_DOMAIN).chain_update(token).finalize();
+    let mut masked = key.0;
+    masked.iter_mut().zip(pad.iter()).for_each(|(byte, pad)| *byte ^= pad);
+    SessionKey(masked)
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        SessionRegistry::new(Arc::new(MemorySessionStore::new()), DEFAULT_SESSION_TTL)
+    }
+}
@@ -0,0 +1,333 @@
+use pendersen_core::key_exchange::{SessionKey, SESSION_KEY_LENGTH};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::session_registry::Session;
+use crate::user_store::{StoreError, BUSY_TIMEOUT};
+
+// A pending interactive challenge with r1, r2 and c in the group's wire
+// encoding. Expiry is wall-clock time so that processes can share it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredChallenge {
+    pub user: String,
+    pub r1: String,
+    pub r2: String,
+    pub c: String,
+    pub expires_at: SystemTime,
+}
+
+// Short-lived login state: challenges waiting for an answer, non-interactive
+// login proofs already used, and issued sessions. SessionRegistry files each
+// session under a hash of its token, with any session key masked.
+// take_challenge removes the entry even when it has expired, so the caller can
+// tell an expired challenge from an unknown one; get_session hides expired sessions.
+pub trait SessionStore: Send + Sync {
+    fn put_challenge(&self, auth_id: &str, challenge: &StoredChallenge) -> Result<(), StoreError>;
+    fn take_challenge(&self, auth_id: &str) -> Result<Option<StoredChallenge>, StoreError>;
+    fn purge_expired_challenges(&self, now: SystemTime) -> Result<usize, StoreError>;
+
+    // Remember a used login proof until `expires_at`. Returns false, atomically,
+    // when the proof is already remembered and has not expired.
+    fn record_proof(&self, proof_id: &str, expires_at: SystemTime) -> Result<bool, StoreError>;
+    fn purge_expired_proofs(&self, now: SystemTime) -> Result<usize, StoreError>;
+
+    fn put_session(&self, token: &str, session: &Session) -> Result<(), StoreError>;
+    fn get_session(&self, token: &str) -> Result<Option<Session>, StoreError>;
+    fn revoke_session(&self, token: &str) -> Result<bool, StoreError>;
+    fn purge_expired_sessions(&self, now: SystemTime) -> Result<usize, StoreError>;
+}
+
+// Login state that lives and dies with the process
+#[derive(Default)]
+pub struct MemorySessionStore {
+    challenges: Mutex<HashMap<String, StoredChallenge>>,
+    proofs: Mutex<HashMap<String, SystemTime>>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn put_challenge(&self, auth_id: &str, challenge: &StoredChallenge) -> Result<(), StoreError> {
+        self.challenges.lock().unwrap().insert(auth_id.to_string(), challenge.clone());
+        Ok(())
+    }
+
+    fn take_challenge(&self, auth_id: &str) -> Result<Option<StoredChallenge>, StoreError> {
+        Ok(self.challenges.lock().unwrap().remove(auth_id))
+    }
+
+    fn purge_expired_challenges(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let mut challenges = self.challenges.lock().unwrap();
+        let before = challenges.len();
+        challenges.retain(|_, challenge| challenge.expires_at > now);
+        Ok(before - challenges.len())
+    }
+
+    fn record_proof(&self, proof_id: &str, expires_at: SystemTime) -> Result<bool, StoreError> {
+        let mut proofs = self.proofs.lock().unwrap();
+        match proofs.get(proof_id) {
+            Some(until) if *until > SystemTime::now() => Ok(false),
+            _ => {
+                proofs.insert(proof_id.to_string(), expires_at);
+                Ok(true)
+            }
+        }
+    }
+
+    fn purge_expired_proofs(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let mut proofs = self.proofs.lock().unwrap();
+        let before = proofs.len();
+        proofs.retain(|_, until| *until > now);
+        Ok(before - proofs.len())
+    }
+
+    fn put_session(&self, token: &str, session: &Session) -> Result<(), StoreError> {
+        self.sessions.lock().unwrap().insert(token.to_string(), session.clone());
+        Ok(())
+    }
+
+    fn get_session(&self, token: &str) -> Result<Option<Session>, StoreError> {
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions.get(token).filter(|session| session.expires_at > SystemTime::now()).cloned())
+    }
+
+    fn revoke_session(&self, token: &str) -> Result<bool, StoreError> {
+        Ok(self.sessions.lock().unwrap().remove(token).is_some())
+    }
+
+    fn purge_expired_sessions(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.expires_at > now);
+        Ok(before - sessions.len())
+    }
+}
+
+// Login state in a SQLite database, so several server processes on one host
+// can hand out challenges and check sessions for each other. Timestamps are
+// stored as Unix milliseconds.
+pub struct SqliteSessionStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteSessionStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let connection = Connection::open(path)?;
+        // Write-ahead logging lets readers in other processes proceed during writes
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(connection)
+    }
+
+    pub fn in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, StoreError> {
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS challenges (
+                auth_id TEXT PRIMARY KEY NOT NULL,
+                user TEXT NOT NULL,
+                r1 TEXT NOT NULL,
+                r2 TEXT NOT NULL,
+                c TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS challenges_expires_at ON challenges (expires_at);
+            CREATE TABLE IF NOT EXISTS login_proofs (
+                proof_id TEXT PRIMARY KEY NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS login_proofs_expires_at ON login_proofs (expires_at);
+            CREATE TABLE IF NOT EXISTS sessions (
+                token TEXT PRIMARY KEY NOT NULL,
+                user TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                key TEXT
+            );
+            CREATE INDEX IF NOT EXISTS sessions_expires_at ON sessions (expires_at);",
+        )?;
+        // Databases created before session keys existed lack the column
+        let has_key = connection
+            .prepare("SELECT 1 FROM pragma_table_info('sessions') WHERE name = 'key'")?
+            .exists([])?;
+        if !has_key {
+            connection.execute_batch("ALTER TABLE sessions ADD COLUMN key TEXT")?;
+        }
+        Ok(SqliteSessionStore {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn put_challenge(&self, auth_id: &str, challenge: &StoredChallenge) -> Result<(), StoreError> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO challenges (auth_id, user, r1, r2, c, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![auth_id, challenge.user, challenge.r1, challenge.r2, challenge.c, to_millis(challenge.expires_at)],
+        )?;
+        Ok(())
+    }
+
+    // A single DELETE ... RETURNING, so two processes can never both take the same challenge
+    fn take_challenge(&self, auth_id: &str) -> Result<Option<StoredChallenge>, StoreError> {
+        let challenge = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "DELETE FROM challenges WHERE auth_id = ?1 RETURNING user, r1, r2, c, expires_at",
+                params![auth_id],
+                |row| {
+                    Ok(StoredChallenge {
+                        user: row.get(0)?,
+                        r1: row.get(1)?,
+                        r2: row.get(2)?,
+                        c: row.get(3)?,
+                        expires_at: from_millis(row.get(4)?),
+                    })
+                },
+            )
+            .optional()?;
+        Ok(challenge)
+    }
+
+    fn purge_expired_challenges(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let purged = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM challenges WHERE expires_at <= ?1", params![to_millis(now)])?;
+        Ok(purged)
+    }
+
+    // One upsert that only overwrites an expired entry, so two processes can
+    // never both accept the same proof
+    fn record_proof(&self, proof_id: &str, expires_at: SystemTime) -> Result<bool, StoreError> {
+        let recorded = self.connection.lock().unwrap().execute(
+            "INSERT INTO login_proofs (proof_id, expires_at) VALUES (?1, ?2)
+             ON CONFLICT (proof_id) DO UPDATE SET expires_at = excluded.expires_at
+             WHERE login_proofs.expires_at <= ?3",
+            params![proof_id, to_millis(expires_at), to_millis(SystemTime::now())],
+        )?;
+        Ok(recorded == 1)
+    }
+
+    fn purge_expired_proofs(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let purged = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM login_proofs WHERE expires_at <= ?1", params![to_millis(now)])?;
+        Ok(purged)
+    }
+
+    fn put_session(&self, token: &str, session: &Session) -> Result<(), StoreError> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO sessions (token, user, expires_at, key) VALUES (?1, ?2, ?3, ?4)",
+            params![token, session.user, to_millis(session.expires_at), session.key.as_ref().map(|key| hex::encode(key.0))],
+        )?;
+        Ok(())
+    }
+
+    fn get_session(&self, token: &str) -> Result<Option<Session>, StoreError> {
+        let session = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT user, expires_at, key FROM sessions WHERE token = ?1 AND expires_at > ?2",
+                params![token, to_millis(SystemTime::now())],
+                |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get::<_, Option<String>>(2)?)),
+            )
+            .optional()?;
+        session
+            .map(|(user, expires_at, key)| {
+                Ok(Session {
+                    user,
+                    expires_at: from_millis(expires_at),
+                    key: key.map(|key| decode_session_key(&key)).transpose()?,
+                })
+            })
+            .transpose()
+    }
+
+    fn revoke_session(&self, token: &str) -> Result<bool, StoreError> {
+        let revoked = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM sessions WHERE token = ?1", params![token])?;
+        Ok(revoked == 1)
+    }
+
+    fn purge_expired_sessions(&self, now: SystemTime) -> Result<usize, StoreError> {
+        let purged = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM sessions WHERE expires_at <= ?1", params![to_millis(now)])?;
+        Ok(purged)
+    }
+}
+
+fn decode_session_key(key: &str) -> Result<SessionKey, StoreError> {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| <[u8; SESSION_KEY_LENGTH]>::try_from(bytes).ok())
+        .map(SessionKey)
+        .ok_or_else(|| StoreError::Corrupt("session key is not 32 hex-encoded bytes".to_string()))
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+// Which SessionStore the server opens: "memory" or "sqlite:<path>"
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SessionStoreConfig {
+    #[default]
+    Memory,
+    Sqlite(PathBuf),
+}
+
+impl SessionStoreConfig {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once(':') {
+            _ if spec == "memory" => Ok(SessionStoreConfig::Memory),
+            Some(("sqlite", path)) if !path.is_empty() => Ok(SessionStoreConfig::Sqlite(PathBuf::from(path))),
+            _ => Err(format!("invalid session store {:?}: expected \"memory\" or \"sqlite:<path>\"", spec)),
+        }
+    }
+
+    pub fn open(&self) -> Result<Arc<dyn SessionStore>, StoreError> {
+        Ok(match self {
+            SessionStoreConfig::Memory => Arc::new(MemorySessionStore::new()),
+            SessionStoreConfig::Sqlite(path) => Arc::new(SqliteSessionStore::open(path)?),
+        })
+    }
+}
+
+impl fmt::Display for SessionStoreConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionStoreConfig::Memory => write!(f, "in-memory session store"),
+            SessionStoreConfig::Sqlite(path) => write!(f, "SQLite session store {}", path.display()),
+        }
+    }
+}
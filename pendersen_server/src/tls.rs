@@ -0,0 +1,126 @@
+use pendersen_core::protocol::{CHANNEL_BINDING_EXPORTER_LABEL, CHANNEL_BINDING_LENGTH};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{rustls::Session, server::TlsStream, TlsAcceptor};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::Connected;
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+// How long a client may take over the TLS handshake before it is dropped
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Finished handshakes waiting for the gRPC server to pick them up
+const ACCEPT_BACKLOG: usize = 64;
+
+// TLS exporter value of one connection. Challenges answered on the connection
+// are bound to it, so a proof relayed from another TLS session fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelBinding(pub [u8; CHANNEL_BINDING_LENGTH]);
+
+impl ChannelBinding {
+    pub fn export(session: &impl Session) -> io::Result<Self> {
+        let mut binding = [0; CHANNEL_BINDING_LENGTH];
+        session
+            .export_keying_material(&mut binding, CHANNEL_BINDING_EXPORTER_LABEL, None)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(ChannelBinding(binding))
+    }
+}
+
+// Request extension describing the TLS connection a request arrived on
+#[derive(Clone, Debug)]
+pub struct ConnectionInfo {
+    pub remote_addr: Option<SocketAddr>,
+    pub channel_binding: ChannelBinding,
+}
+
+// Server side of a TLS connection whose exporter value was read after the handshake
+pub struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    info: ConnectionInfo,
+}
+
+impl TlsConnection {
+    pub async fn accept(acceptor: &TlsAcceptor, stream: TcpStream) -> io::Result<Self> {
+        let remote_addr = stream.peer_addr().ok();
+        let stream = acceptor.accept(stream).await?;
+        let channel_binding = ChannelBinding::export(stream.get_ref().1)?;
+        Ok(TlsConnection {
+            stream,
+            info: ConnectionInfo {
+                remote_addr,
+                channel_binding,
+            },
+        })
+    }
+}
+
+impl Connected for TlsConnection {
+    type ConnectInfo = ConnectionInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.info.clone()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+// Accept connections on `listener` and complete their TLS handshakes, each on
+// its own task so a slow client cannot hold up the others. Failed handshakes
+// are logged and dropped rather than ending the stream, which would stop the
+// server. Pass the result to Router::serve_with_incoming.
+pub fn tls_incoming(listener: TcpListener, acceptor: TlsAcceptor) -> ReceiverStream<io::Result<TlsConnection>> {
+    let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+    tokio::spawn(async move {
+        while !sender.is_closed() {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // Usually out of file descriptors; give connections time to close
+                    log::error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, TlsConnection::accept(&acceptor, stream)).await {
+                    Ok(Ok(connection)) => {
+                        let _ = sender.send(Ok(connection)).await;
+                    }
+                    Ok(Err(e)) => log::warn!("TLS handshake with {} failed: {}", addr, e),
+                    Err(_) => log::warn!("TLS handshake with {} timed out", addr),
+                }
+            });
+        }
+    });
+    ReceiverStream::new(receiver)
+}
@@ -0,0 +1,466 @@
+use pendersen_core::KdfParameters;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+// How long a process waits for another one holding a SQLite database lock
+pub(crate) const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A registered user as the stores keep it: y1 and y2 in the group's wire
+// encoding, so the stores do not depend on which group the server runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredUser {
+    pub y1: String,
+    pub y2: String,
+    pub kdf: Option<KdfParameters>,
+}
+
+// Failure of a UserStore or SessionStore backend
+#[derive(Debug)]
+pub enum StoreError {
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    Corrupt(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "store I/O error: {}", e),
+            StoreError::Sqlite(e) => write!(f, "store SQLite error: {}", e),
+            StoreError::Corrupt(message) => write!(f, "store is corrupt: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(e: std::io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
+
+// Where AuthService keeps registrations. insert_if_absent, update and replace
+// report whether they changed anything instead of failing on a missing or taken name.
+pub trait UserStore: Send + Sync {
+    fn get(&self, user: &str) -> Result<Option<StoredUser>, StoreError>;
+    fn insert_if_absent(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError>;
+    fn update(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError>;
+    // Compare-and-swap: overwrite the user's record only while it still equals `expected`
+    fn replace(&self, user: &str, expected: &StoredUser, replacement: &StoredUser) -> Result<bool, StoreError>;
+    fn delete(&self, user: &str) -> Result<bool, StoreError>;
+    fn list(&self) -> Result<Vec<String>, StoreError>;
+}
+
+// Registrations that last as long as the process
+#[derive(Default)]
+pub struct MemoryUserStore {
+    users: Mutex<HashMap<String, StoredUser>>,
+}
+
+impl MemoryUserStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UserStore for MemoryUserStore {
+    fn get(&self, user: &str) -> Result<Option<StoredUser>, StoreError> {
+        Ok(self.users.lock().unwrap().get(user).cloned())
+    }
+
+    fn insert_if_absent(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        Ok(insert_if_absent(&mut self.users.lock().unwrap(), user, record))
+    }
+
+    fn update(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        Ok(update(&mut self.users.lock().unwrap(), user, record))
+    }
+
+    fn replace(&self, user: &str, expected: &StoredUser, replacement: &StoredUser) -> Result<bool, StoreError> {
+        Ok(replace(&mut self.users.lock().unwrap(), user, expected, replacement))
+    }
+
+    fn delete(&self, user: &str) -> Result<bool, StoreError> {
+        Ok(self.users.lock().unwrap().remove(user).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        Ok(sorted_names(&self.users.lock().unwrap()))
+    }
+}
+
+// All registrations in one JSON file, rewritten in full on every change.
+// Writes go to a temporary file in the same directory which is synced and
+// then renamed over the original, so a crash leaves the old or new file.
+pub struct JsonFileUserStore {
+    path: PathBuf,
+    users: Mutex<HashMap<String, StoredUser>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UserFile {
+    users: BTreeMap<String, UserRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UserRecord {
+    y1: String,
+    y2: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KdfRecord {
+    salt: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl JsonFileUserStore {
+    // Load the file at `path`, starting empty if it does not exist yet
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let users = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let file: UserFile = serde_json::from_str(&contents)
+                    .map_err(|e| StoreError::Corrupt(format!("{}: {}", path.display(), e)))?;
+                file.users
+                    .into_iter()
+                    .map(|(name, record)| Ok((name, record.try_into()?)))
+                    .collect::<Result<_, StoreError>>()?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(JsonFileUserStore {
+            path,
+            users: Mutex::new(users),
+        })
+    }
+
+    fn save(&self, users: &HashMap<String, StoredUser>) -> Result<(), StoreError> {
+        let file = UserFile {
+            users: users.iter().map(|(name, user)| (name.clone(), user.into())).collect(),
+        };
+        let json = serde_json::to_string_pretty(&file).map_err(|e| StoreError::Corrupt(e.to_string()))?;
+
+        let mut tmp_name = self.path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = self.path.with_file_name(tmp_name);
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(json.as_bytes())?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    // Apply `change` to a copy of the map and only keep it once it is on disk
+    fn modify<F>(&self, change: F) -> Result<bool, StoreError>
+    where
+        F: FnOnce(&mut HashMap<String, StoredUser>) -> bool,
+    {
+        let mut users = self.users.lock().unwrap();
+        let mut updated = users.clone();
+        if !change(&mut updated) {
+            return Ok(false);
+        }
+        self.save(&updated)?;
+        *users = updated;
+        Ok(true)
+    }
+}
+
+impl UserStore for JsonFileUserStore {
+    fn get(&self, user: &str) -> Result<Option<StoredUser>, StoreError> {
+        Ok(self.users.lock().unwrap().get(user).cloned())
+    }
+
+    fn insert_if_absent(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        self.modify(|users| insert_if_absent(users, user, record))
+    }
+
+    fn update(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        self.modify(|users| update(users, user, record))
+    }
+
+    fn replace(&self, user: &str, expected: &StoredUser, replacement: &StoredUser) -> Result<bool, StoreError> {
+        self.modify(|users| replace(users, user, expected, replacement))
+    }
+
+    fn delete(&self, user: &str) -> Result<bool, StoreError> {
+        self.modify(|users| users.remove(user).is_some())
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        Ok(sorted_names(&self.users.lock().unwrap()))
+    }
+}
+
+impl From<&StoredUser> for UserRecord {
+    fn from(user: &StoredUser) -> Self {
+        UserRecord {
+            y1: user.y1.clone(),
+            y2: user.y2.clone(),
+            kdf: user.kdf.as_ref().map(|kdf| KdfRecord {
+                salt: hex::encode(&kdf.salt),
+                memory_kib: kdf.memory_kib,
+                iterations: kdf.iterations,
+                parallelism: kdf.parallelism,
+            }),
+        }
+    }
+}
+
+impl TryFrom<UserRecord> for StoredUser {
+    type Error = StoreError;
+
+    fn try_from(record: UserRecord) -> Result<Self, StoreError> {
+        let kdf = match record.kdf {
+            Some(kdf) => Some(KdfParameters {
+                salt: hex::decode(&kdf.salt).map_err(|e| StoreError::Corrupt(format!("invalid salt: {}", e)))?,
+                memory_kib: kdf.memory_kib,
+                iterations: kdf.iterations,
+                parallelism: kdf.parallelism,
+            }),
+            None => None,
+        };
+        Ok(StoredUser {
+            y1: record.y1,
+            y2: record.y2,
+            kdf,
+        })
+    }
+}
+
+// Registrations in a SQLite database, one row per user
+pub struct SqliteUserStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteUserStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let connection = Connection::open(path)?;
+        // Write-ahead logging lets readers in other processes proceed during writes
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        Self::with_connection(connection)
+    }
+
+    pub fn in_memory() -> Result<Self, StoreError> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, StoreError> {
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS users (
+                name TEXT PRIMARY KEY NOT NULL,
+                y1 TEXT NOT NULL,
+                y2 TEXT NOT NULL,
+                kdf_salt BLOB,
+                kdf_memory_kib INTEGER,
+                kdf_iterations INTEGER,
+                kdf_parallelism INTEGER
+            )",
+        )?;
+        Ok(SqliteUserStore {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl UserStore for SqliteUserStore {
+    fn get(&self, user: &str) -> Result<Option<StoredUser>, StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let row = connection
+            .query_row(
+                "SELECT y1, y2, kdf_salt, kdf_memory_kib, kdf_iterations, kdf_parallelism FROM users WHERE name = ?1",
+                params![user],
+                |row| {
+                    let salt: Option<Vec<u8>> = row.get(2)?;
+                    let kdf = match salt {
+                        Some(salt) => Some(KdfParameters {
+                            salt,
+                            memory_kib: row.get(3)?,
+                            iterations: row.get(4)?,
+                            parallelism: row.get(5)?,
+                        }),
+                        None => None,
+                    };
+                    Ok(StoredUser {
+                        y1: row.get(0)?,
+                        y2: row.get(1)?,
+                        kdf,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(row)
+    }
+
+    fn insert_if_absent(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        let (salt, memory_kib, iterations, parallelism) = kdf_columns(record);
+        let inserted = self.connection.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO users (name, y1, y2, kdf_salt, kdf_memory_kib, kdf_iterations, kdf_parallelism)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![user, record.y1, record.y2, salt, memory_kib, iterations, parallelism],
+        )?;
+        Ok(inserted == 1)
+    }
+
+    fn update(&self, user: &str, record: &StoredUser) -> Result<bool, StoreError> {
+        let (salt, memory_kib, iterations, parallelism) = kdf_columns(record);
+        let updated = self.connection.lock().unwrap().execute(
+            "UPDATE users SET y1 = ?2, y2 = ?3, kdf_salt = ?4, kdf_memory_kib = ?5, kdf_iterations = ?6, kdf_parallelism = ?7
+             WHERE name = ?1",
+            params![user, record.y1, record.y2, salt, memory_kib, iterations, parallelism],
+        )?;
+        Ok(updated == 1)
+    }
+
+    fn replace(&self, user: &str, expected: &StoredUser, replacement: &StoredUser) -> Result<bool, StoreError> {
+        let (salt, memory_kib, iterations, parallelism) = kdf_columns(replacement);
+        let (old_salt, old_memory_kib, old_iterations, old_parallelism) = kdf_columns(expected);
+        // A single statement, so another process cannot change the row between the check and the write
+        let replaced = self.connection.lock().unwrap().execute(
+            "UPDATE users SET y1 = ?2, y2 = ?3, kdf_salt = ?4, kdf_memory_kib = ?5, kdf_iterations = ?6, kdf_parallelism = ?7
+             WHERE name = ?1 AND y1 = ?8 AND y2 = ?9 AND kdf_salt IS ?10 AND kdf_memory_kib IS ?11
+               AND kdf_iterations IS ?12 AND kdf_parallelism IS ?13",
+            params![
+                user,
+                replacement.y1,
+                replacement.y2,
+                salt,
+                memory_kib,
+                iterations,
+                parallelism,
+                expected.y1,
+                expected.y2,
+                old_salt,
+                old_memory_kib,
+                old_iterations,
+                old_parallelism
+            ],
+        )?;
+        Ok(replaced == 1)
+    }
+
+    fn delete(&self, user: &str) -> Result<bool, StoreError> {
+        let deleted = self
+            .connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM users WHERE name = ?1", params![user])?;
+        Ok(deleted == 1)
+    }
+
+    fn list(&self) -> Result<Vec<String>, StoreError> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT name FROM users ORDER BY name")?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        Ok(names)
+    }
+}
+
+type KdfColumns<'a> = (Option<&'a [u8]>, Option<u32>, Option<u32>, Option<u32>);
+
+fn kdf_columns(record: &StoredUser) -> KdfColumns<'_> {
+    match &record.kdf {
+        Some(kdf) => (Some(&kdf.salt), Some(kdf.memory_kib), Some(kdf.iterations), Some(kdf.parallelism)),
+        None => (None, None, None, None),
+    }
+}
+
+fn insert_if_absent(users: &mut HashMap<String, StoredUser>, user: &str, record: &StoredUser) -> bool {
+    if users.contains_key(user) {
+        return false;
+    }
+    users.insert(user.to_string(), record.clone());
+    true
+}
+
+fn update(users: &mut HashMap<String, StoredUser>, user: &str, record: &StoredUser) -> bool {
+    match users.get_mut(user) {
+        Some(existing) => {
+            *existing = record.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+fn replace(users: &mut HashMap<String, StoredUser>, user: &str, expected: &StoredUser, replacement: &StoredUser) -> bool {
+    match users.get_mut(user) {
+        Some(existing) if existing == expected => {
+            *existing = replacement.clone();
+            true
+        }
+        _ => false,
+    }
+}
+
+fn sorted_names(users: &HashMap<String, StoredUser>) -> Vec<String> {
+    let mut names: Vec<String> = users.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+// Which UserStore the server opens: "memory", "json:<path>" or "sqlite:<path>"
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum UserStoreConfig {
+    #[default]
+    Memory,
+    JsonFile(PathBuf),
+    Sqlite(PathBuf),
+}
+
+impl UserStoreConfig {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once(':') {
+            _ if spec == "memory" => Ok(UserStoreConfig::Memory),
+            Some(("json", path)) if !path.is_empty() => Ok(UserStoreConfig::JsonFile(PathBuf::from(path))),
+            Some(("sqlite", path)) if !path.is_empty() => Ok(UserStoreConfig::Sqlite(PathBuf::from(path))),
+            _ => Err(format!(
+                "invalid user store {:?}: expected \"memory\", \"json:<path>\" or \"sqlite:<path>\"",
+                spec
+            )),
+        }
+    }
+
+    pub fn open(&self) -> Result<Arc<dyn UserStore>, StoreError> {
+        Ok(match self {
+            UserStoreConfig::Memory => Arc::new(MemoryUserStore::new()),
+            UserStoreConfig::JsonFile(path) => Arc::new(JsonFileUserStore::open(path)?),
+            UserStoreConfig::Sqlite(path) => Arc::new(SqliteUserStore::open(path)?),
+        })
+    }
+}
+
+impl fmt::Display for UserStoreConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserStoreConfig::Memory => write!(f, "in-memory user store"),
+            UserStoreConfig::JsonFile(path) => write!(f, "JSON user store {}", path.display()),
+            UserStoreConfig::Sqlite(path) => write!(f, "SQLite user store {}", path.display()),
+        }
+    }
+}
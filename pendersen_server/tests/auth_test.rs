@@ -1,31 +1,22 @@
-use pendersen_server::{AuthService, pb};
-use pendersen_server::pb::{
-    RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest,
-};
+use pendersen_core::{protocol, Group, GroupParameters, KdfParameters, RistrettoParameters};
+use pendersen_server::AuthService;
+use pendersen_server::pb::{RegisterRequest, AuthenticationChallengeRequest, AuthenticationAnswerRequest, KdfParametersRequest, ValidateSessionRequest, ChangeCredentialsRequest};
 use pendersen_server::pb::auth_server::Auth; // Import the Auth trait
 
-use tokio;
 use tonic::Request;
-use num_bigint::{BigInt};
-use num_traits::One;
-
+use num_bigint::BigInt;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod common;
+use common::{login_request, now, register, register_request, test_params};
 
 #[tokio::test]
 async fn test_register_user() {
     // Arrange
-    let (G, H, P, Q) = (
-        BigInt::from(2),
-        BigInt::from(3),
-        BigInt::from(17),
-        BigInt::from(19),
-    );
-    let auth_service = AuthService::new(G, H, P, Q);
-    let request = Request::new(RegisterRequest {
-        user: "test_user".to_string(),
-        y1: "123".to_string(),
-        y2: "456".to_string(),
-    });
+    let auth_service = AuthService::new(test_params());
+    let request = Request::new(register_request(&test_params(), &BigInt::from(6), None));
 
     // Act
     let response = auth_service.register(request).await.unwrap();
@@ -38,24 +29,16 @@ async fn test_register_user() {
 #[tokio::test]
 async fn test_create_authentication_challenge() {
     // Arrange
-    let (G, H, P, Q) = (
-        BigInt::from(2),
-        BigInt::from(3),
-        BigInt::from(17),
-        BigInt::from(19),
-    );
-    let auth_service = AuthService::new(G, H, P, Q);
-    let register_request = Request::new(RegisterRequest {
-        user: "test_user".to_string(),
-        y1: "123".to_string(),
-        y2: "456".to_string(),
-    });
-    auth_service.register(register_request).await.unwrap();
+    let auth_service = AuthService::new(test_params());
+    auth_service
+        .register(Request::new(register_request(&test_params(), &BigInt::from(6), None)))
+        .await
+        .unwrap();
 
     let auth_request = Request::new(AuthenticationChallengeRequest {
         user: "test_user".to_string(),
-        r1: "789".to_string(),
-        r2: "101112".to_string(),
+        r1: "3".to_string(),
+        r2: "13".to_string(),
     });
 
     // Act
@@ -66,5 +49,833 @@ async fn test_create_authentication_challenge() {
     let auth_id = response.into_inner().auth_id;
 
     // Assert
-    assert_eq!(auth_id, "auth123");
-}
\ No newline at end of file
+    assert_eq!(auth_id.len(), 32);
+    assert!(auth_id.chars().all(|c| c.is_ascii_hexdigit()));
+}
+#[tokio::test]
+async fn test_verify_authentication_with_valid_proof() {
+    // Arrange
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone());
+    let x = BigInt::from(6);
+    auth_service
+        .register(Request::new(register_request(&params, &x, None)))
+        .await
+        .unwrap();
+
+    let k = BigInt::from(7);
+    let (r1, r2) = protocol::commitment(&params, &k);
+    let challenge = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: r1.to_string(),
+            r2: r2.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let c: BigInt = challenge.c.parse().unwrap();
+    let s = protocol::response(&params, &k, &c, &x);
+
+    // Act
+    let response = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id,
+            s: s.to_string(),
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!response.into_inner().session_id.is_empty());
+}
+
+// Register x = 6 in the toy group and open a challenge with k = 7, returning (auth_id, c)
+async fn open_challenge(auth_service: &AuthService<GroupParameters>) -> (String, BigInt) {
+    register(auth_service, &test_params(), &BigInt::from(6)).await;
+    next_challenge(auth_service).await
+}
+
+// Another k = 7 challenge for the already registered test_user
+async fn next_challenge(auth_service: &AuthService<GroupParameters>) -> (String, BigInt) {
+    let params = test_params();
+    let (r1, r2) = protocol::commitment(&params, &BigInt::from(7));
+    let challenge = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: r1.to_string(),
+            r2: r2.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    (challenge.auth_id, challenge.c.parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_verify_authentication_with_wrong_proof_is_unauthenticated() {
+    // Arrange
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone());
+    let (auth_id, c) = open_challenge(&auth_service).await;
+    // Off by one from the honest answer, so it fails whatever c the server drew
+    let s: BigInt = (protocol::response(&params, &BigInt::from(7), &c, &BigInt::from(6)) + 1) % 11;
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id, s: s.to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_verify_authentication_names_unparsable_field() {
+    // Arrange
+    let auth_service = AuthService::new(test_params());
+    let (auth_id, _) = open_challenge(&auth_service).await;
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id, s: "12abc".to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().starts_with("invalid s:"));
+}
+
+#[tokio::test]
+async fn test_verify_authentication_for_unknown_auth_id() {
+    // Arrange
+    let auth_service = AuthService::new(test_params());
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: "missing".to_string(),
+            s: "1".to_string(),
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_challenge_for_unknown_user_is_not_found() {
+    // Arrange
+    let auth_service = AuthService::new(test_params());
+
+    // Act
+    let status = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "nobody".to_string(),
+            r1: "4".to_string(),
+            r2: "9".to_string(),
+        }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_register_rejects_small_subgroup_elements() {
+    // Arrange: 22 = p - 1 has order 2, 5 has order 22, 0 and 23 are out of range
+    let auth_service = AuthService::new(test_params());
+
+    for (field, y1, y2) in [("y1", "22", "9"), ("y2", "4", "5"), ("y1", "0", "9"), ("y2", "4", "23")] {
+        let mut request = register_request(&test_params(), &BigInt::from(6), None);
+        request.y1 = y1.to_string();
+        request.y2 = y2.to_string();
+
+        // Act
+        let status = auth_service.register(Request::new(request)).await.unwrap_err();
+
+        // Assert
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().starts_with(&format!("invalid {}:", field)), "{}", status.message());
+    }
+}
+
+#[tokio::test]
+async fn test_challenge_rejects_small_subgroup_commitment() {
+    // Arrange
+    let auth_service = AuthService::new(test_params());
+    register(&auth_service, &test_params(), &BigInt::from(6)).await;
+
+    for (field, r1, r2) in [("r1", "22", "13"), ("r2", "3", "-9"), ("r1", "1000", "13")] {
+        // Act
+        let status = auth_service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "test_user".to_string(),
+                r1: r1.to_string(),
+                r2: r2.to_string(),
+            }))
+            .await
+            .unwrap_err();
+
+        // Assert
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().starts_with(&format!("invalid {}:", field)), "{}", status.message());
+    }
+}
+
+#[tokio::test]
+async fn test_verify_authentication_rejects_out_of_range_response() {
+    // Arrange: s = c + q would verify like s = c if it were reduced
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone());
+    let (auth_id, c) = open_challenge(&auth_service).await;
+    let s: BigInt = protocol::response(&params, &BigInt::from(7), &c, &BigInt::from(6)) + 11;
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id, s: s.to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().starts_with("invalid s:"));
+}
+
+#[tokio::test]
+async fn test_challenge_cannot_be_replayed() {
+    // Arrange
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone());
+    let (auth_id, c) = open_challenge(&auth_service).await;
+    let s = protocol::response(&params, &BigInt::from(7), &c, &BigInt::from(6));
+    let answer = AuthenticationAnswerRequest { auth_id, s: s.to_string(), client_share: String::new() };
+    auth_service.verify_authentication(Request::new(answer.clone())).await.unwrap();
+
+    // Act
+    let status = auth_service.verify_authentication(Request::new(answer)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_failed_attempt_consumes_challenge() {
+    // Arrange
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone());
+    let (auth_id, c) = open_challenge(&auth_service).await;
+    let right = protocol::response(&params, &BigInt::from(7), &c, &BigInt::from(6));
+    let wrong: BigInt = (&right + 1) % 11;
+    auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id: auth_id.clone(), s: wrong.to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id, s: right.to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_expired_challenge_is_rejected() {
+    // Arrange
+    let params = test_params();
+    let auth_service = AuthService::new(params.clone()).with_challenge_ttl(Duration::ZERO);
+    let (auth_id, c) = open_challenge(&auth_service).await;
+    let s = protocol::response(&params, &BigInt::from(7), &c, &BigInt::from(6));
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id, s: s.to_string(), client_share: String::new() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_reaper_evicts_expired_challenges() {
+    // Arrange
+    let expiring = AuthService::new(test_params()).with_challenge_ttl(Duration::ZERO);
+    let live = AuthService::new(test_params());
+    for auth_service in [&expiring, &live] {
+        open_challenge(auth_service).await;
+        next_challenge(auth_service).await;
+        next_challenge(auth_service).await;
+    }
+
+    // Act
+    let evicted = expiring.reap_expired_challenges().unwrap();
+    let kept = live.reap_expired_challenges().unwrap();
+
+    // Assert
+    assert_eq!(evicted, 3);
+    assert_eq!(kept, 0);
+}
+
+#[tokio::test]
+async fn test_background_reaper_runs() {
+    // Arrange
+    let auth_service = AuthService::new(test_params()).with_challenge_ttl(Duration::from_millis(10));
+    open_challenge(&auth_service).await;
+
+    // Act
+    let reaper = auth_service.spawn_challenge_reaper(Duration::from_millis(5));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    reaper.abort();
+
+    // Assert
+    assert_eq!(auth_service.reap_expired_challenges().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_verify_authentication_over_ristretto255() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(123456789));
+    auth_service
+        .register(Request::new(register_request(&group, &x, None)))
+        .await
+        .unwrap();
+
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let challenge = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let c = group.decode_scalar(&challenge.c).unwrap();
+    let s = group.encode_scalar(&protocol::response(&group, &k, &c, &x));
+
+    // Act
+    let response = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id,
+            s,
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!response.into_inner().session_id.is_empty());
+}
+
+#[tokio::test]
+async fn test_register_rejects_undecodable_element() {
+    // Arrange
+    let auth_service = AuthService::new(RistrettoParameters::default());
+    let request = Request::new(RegisterRequest {
+        user: "test_user".to_string(),
+        y1: "123".to_string(),
+        y2: "456".to_string(),
+        ..Default::default()
+    });
+
+    // Act
+    let status = auth_service.register(request).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().contains("y1"));
+}
+
+#[tokio::test]
+async fn test_register_rejects_unequal_logarithms() {
+    // Arrange: y1 = g^6 but y2 = h^5, with a proof built for x = 6
+    let group = test_params();
+    let auth_service = AuthService::new(group.clone());
+    let mut request = register_request(&group, &BigInt::from(6), None);
+    let (_, y2) = protocol::public_key(&group, &BigInt::from(5));
+    request.y2 = y2.to_string();
+
+    // Act
+    let status = auth_service.register(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    let status = auth_service
+        .get_kdf_parameters(Request::new(KdfParametersRequest { user: "test_user".to_string() }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_register_proof_is_bound_to_user() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let mut request = register_request(&group, &group.scalar_from_bigint(&BigInt::from(42)), None);
+    request.user = "someone_else".to_string();
+
+    // Act
+    let status = auth_service.register(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_register_requires_proof() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let mut request = register_request(&group, &group.scalar_from_bigint(&BigInt::from(42)), None);
+    request.s = String::new();
+
+    // Act
+    let status = auth_service.register(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().contains("s"));
+}
+
+// Prove knowledge of `password` against the key for `x` while proposing the key for `new_x`
+fn change_request<G: Group>(group: &G, x: &G::Scalar, password: &G::Scalar, new_x: &G::Scalar) -> ChangeCredentialsRequest {
+    change_request_for_key(group, x, password, new_x, protocol::public_key(group, new_x))
+}
+
+// The same, proposing (new_y1, new_y2) with a registration proof made with `new_x`
+fn change_request_for_key<G: Group>(
+    group: &G,
+    x: &G::Scalar,
+    password: &G::Scalar,
+    new_x: &G::Scalar,
+    (new_y1, new_y2): (G::Element, G::Element),
+) -> ChangeCredentialsRequest {
+    let (y1, y2) = protocol::public_key(group, x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let timestamp = now();
+    let c = protocol::change_credentials_challenge(
+        group,
+        "test_user",
+        (&y1, &y2),
+        (&r1, &r2),
+        (&new_y1, &new_y2),
+        None,
+        &timestamp.to_be_bytes(),
+    );
+    let new_k = group.random_scalar();
+    let (new_r1, new_r2) = protocol::commitment(group, &new_k);
+    let new_c = protocol::registration_challenge(group, "test_user", (&new_y1, &new_y2), (&new_r1, &new_r2), None);
+    ChangeCredentialsRequest {
+        user: "test_user".to_string(),
+        r1: group.encode_element(&r1),
+        r2: group.encode_element(&r2),
+        s: group.encode_scalar(&protocol::response(group, &k, &c, password)),
+        timestamp,
+        new_y1: group.encode_element(&new_y1),
+        new_y2: group.encode_element(&new_y2),
+        kdf: None,
+        new_r1: group.encode_element(&new_r1),
+        new_r2: group.encode_element(&new_r2),
+        new_s: group.encode_scalar(&protocol::response(group, &new_k, &new_c, new_x)),
+    }
+}
+
+#[tokio::test]
+async fn test_non_interactive_login() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    let response = auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(!response.into_inner().session_id.is_empty());
+}
+
+#[tokio::test]
+async fn test_non_interactive_login_with_bad_password() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    let status = auth_service
+        .login(Request::new(login_request(&group, &x, &group.scalar_from_bigint(&BigInt::from(41)), now())))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_non_interactive_login_rejects_stale_timestamp() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    let status = auth_service
+        .login(Request::new(login_request(&group, &x, &x, now() - 3600)))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn test_non_interactive_login_cannot_be_replayed() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let request = login_request(&group, &x, &x, now());
+    auth_service.login(Request::new(request.clone())).await.unwrap();
+
+    // Act
+    let status = auth_service.login(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_session_token_validates_to_user() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let login = auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Act
+    let response = auth_service
+        .validate_session(Request::new(ValidateSessionRequest { session_id: login.session_id.clone() }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    assert_eq!(login.session_id.len(), 64);
+    assert_eq!(response.user, "test_user");
+    assert_eq!(response.expires_at, login.expires_at);
+    assert!(response.expires_at > now());
+}
+
+#[tokio::test]
+async fn test_unknown_session_token_is_rejected() {
+    // Arrange
+    let auth_service = AuthService::new(test_params());
+
+    // Act
+    let status = auth_service
+        .validate_session(Request::new(ValidateSessionRequest { session_id: "session123".to_string() }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_expired_session_token_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone()).with_session_ttl(Duration::ZERO);
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let login = auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Act
+    let status = auth_service
+        .validate_session(Request::new(ValidateSessionRequest { session_id: login.session_id }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_register_existing_user_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let other = group.scalar_from_bigint(&BigInt::from(43));
+
+    // Act
+    let status = auth_service
+        .register(Request::new(register_request(&group, &other, None)))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::AlreadyExists);
+    auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_change_credentials_with_old_secret() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let new_x = group.scalar_from_bigint(&BigInt::from(43));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    auth_service
+        .change_credentials(Request::new(change_request(&group, &x, &x, &new_x)))
+        .await
+        .unwrap();
+
+    // Assert
+    auth_service
+        .login(Request::new(login_request(&group, &new_x, &new_x, now())))
+        .await
+        .unwrap();
+    let status = auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_change_credentials_with_wrong_secret_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let attacker = group.scalar_from_bigint(&BigInt::from(7));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    let status = auth_service
+        .change_credentials(Request::new(change_request(&group, &x, &attacker, &attacker)))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_change_credentials_proof_binds_new_key() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let mut request = change_request(&group, &x, &x, &group.scalar_from_bigint(&BigInt::from(43)));
+    // A well-formed key and proof of the attacker's own, swapped in after the old-secret proof
+    let attacker = change_request(&group, &x, &x, &group.scalar_from_bigint(&BigInt::from(7)));
+    request.new_y1 = attacker.new_y1;
+    request.new_y2 = attacker.new_y2;
+    request.new_r1 = attacker.new_r1;
+    request.new_r2 = attacker.new_r2;
+    request.new_s = attacker.new_s;
+
+    // Act
+    let status = auth_service.change_credentials(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_change_credentials_rejects_unrelated_new_key() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (new_x, other) = (group.scalar_from_bigint(&BigInt::from(43)), group.scalar_from_bigint(&BigInt::from(44)));
+    let (new_y1, _) = protocol::public_key(&group, &new_x);
+    let (_, new_y2) = protocol::public_key(&group, &other);
+    let request = change_request_for_key(&group, &x, &x, &new_x, (new_y1, new_y2));
+
+    // Act
+    let status = auth_service.change_credentials(Request::new(request)).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    auth_service
+        .login(Request::new(login_request(&group, &x, &x, now())))
+        .await
+        .unwrap();
+}
+
+fn test_kdf() -> KdfParameters {
+    KdfParameters {
+        salt: b"0123456789abcdef".to_vec(),
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    }
+}
+
+#[tokio::test]
+async fn test_kdf_parameters_are_returned_after_registration() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = test_kdf().derive_secret(&group, "correct horse battery staple").unwrap();
+    auth_service
+        .register(Request::new(register_request(&group, &x, Some(test_kdf()))))
+        .await
+        .unwrap();
+
+    // Act
+    let response = auth_service
+        .get_kdf_parameters(Request::new(KdfParametersRequest { user: "test_user".to_string() }))
+        .await
+        .unwrap();
+
+    // Assert
+    let kdf = KdfParameters::from(response.into_inner().kdf.unwrap());
+    assert_eq!(kdf, test_kdf());
+    assert_eq!(kdf.derive_secret(&group, "correct horse battery staple").unwrap(), x);
+}
+
+#[tokio::test]
+async fn test_kdf_parameters_for_unknown_user() {
+    let auth_service = AuthService::new(test_params());
+
+    let status = auth_service
+        .get_kdf_parameters(Request::new(KdfParametersRequest { user: "nobody".to_string() }))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}
+
+#[tokio::test]
+async fn test_register_rejects_weak_kdf_parameters() {
+    let auth_service = AuthService::new(test_params());
+    let mut kdf = test_kdf();
+    kdf.salt = b"salt".to_vec();
+
+    let status = auth_service
+        .register(Request::new(register_request(&test_params(), &BigInt::from(1), Some(kdf))))
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_challenges_get_unique_auth_ids() {
+    // Arrange
+    let group = test_params();
+    let auth_service = Arc::new(AuthService::new(group.clone()));
+    let x = BigInt::from(6);
+    register(&auth_service, &group, &x).await;
+
+    // Act: start 64 logins for the same user at once
+    let handles: Vec<_> = (0..64)
+        .map(|_| {
+            let auth_service = auth_service.clone();
+            let group = group.clone();
+            tokio::spawn(async move {
+                let k = group.random_scalar();
+                let (r1, r2) = protocol::commitment(&group, &k);
+                let challenge = auth_service
+                    .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                        user: "test_user".to_string(),
+                        r1: group.encode_element(&r1),
+                        r2: group.encode_element(&r2),
+                    }))
+                    .await
+                    .unwrap()
+                    .into_inner();
+                (k, challenge)
+            })
+        })
+        .collect();
+    let mut challenges = Vec::new();
+    for handle in handles {
+        challenges.push(handle.await.unwrap());
+    }
+
+    // Assert: no login overwrote another, so every one of them still verifies
+    let auth_ids: HashSet<_> = challenges.iter().map(|(_, challenge)| challenge.auth_id.clone()).collect();
+    assert_eq!(auth_ids.len(), 64);
+    for (k, challenge) in challenges {
+        let c: BigInt = challenge.c.parse().unwrap();
+        let response = auth_service
+            .verify_authentication(Request::new(AuthenticationAnswerRequest {
+                auth_id: challenge.auth_id,
+                s: protocol::response(&group, &k, &c, &x).to_string(),
+                client_share: String::new(),
+            }))
+            .await
+            .unwrap();
+        assert!(!response.into_inner().session_id.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_challenge_bit_length_is_configurable() {
+    let group = GroupParameters::load("../parameters.json").unwrap();
+    let auth_service = AuthService::new(group.clone()).with_challenge_bits(20);
+    register(&auth_service, &group, &BigInt::from(6)).await;
+    let (r1, r2) = protocol::commitment(&group, &BigInt::from(7));
+
+    for _ in 0..32 {
+        let challenge = auth_service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "test_user".to_string(),
+                r1: r1.to_string(),
+                r2: r2.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(challenge.c.parse::<BigInt>().unwrap() < BigInt::from(1 << 20));
+    }
+}
@@ -0,0 +1,219 @@
+use pendersen_core::protocol;
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{AuthenticationAnswerRequest, AuthenticationChallengeRequest};
+use pendersen_server::{
+    AuthService, ChallengeKey, ChallengeTokens, MemorySessionStore, MemoryUserStore, SessionStore, StoredChallenge,
+    TokenError, UserStore,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use num_bigint::BigInt;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::Request;
+
+mod common;
+use common::{register, test_params};
+
+fn challenge() -> StoredChallenge {
+    // Tokens carry millisecond precision
+    let expires_at = SystemTime::now() + Duration::from_secs(60);
+    StoredChallenge {
+        user: "alice".to_string(),
+        r1: "3".to_string(),
+        r2: "13".to_string(),
+        c: "5".to_string(),
+        expires_at: UNIX_EPOCH + Duration::from_millis(expires_at.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64),
+    }
+}
+
+#[test]
+fn test_token_round_trip() {
+    let tokens = ChallengeTokens::new(ChallengeKey::generate());
+    let original = challenge();
+    let token = tokens.seal(&original);
+
+    assert_eq!(tokens.open(&token).unwrap(), original);
+    // Sealing the same challenge twice gives unrelated tokens
+    assert_ne!(tokens.seal(&original), token);
+}
+
+#[test]
+fn test_tampered_token_is_rejected() {
+    let tokens = ChallengeTokens::new(ChallengeKey::generate());
+    let mut bytes = URL_SAFE_NO_PAD.decode(tokens.seal(&challenge())).unwrap();
+
+    // Flip a bit of the ciphertext, then of the key id in the header
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert_eq!(tokens.open(&URL_SAFE_NO_PAD.encode(&bytes)).unwrap_err(), TokenError::Forged);
+    bytes[last] ^= 1;
+    bytes[1] ^= 1;
+    assert_eq!(tokens.open(&URL_SAFE_NO_PAD.encode(&bytes)).unwrap_err(), TokenError::UnknownKey);
+
+    assert!(matches!(tokens.open("not a token"), Err(TokenError::Malformed(_))));
+    assert!(matches!(tokens.open("AQ"), Err(TokenError::Malformed(_))));
+}
+
+#[test]
+fn test_token_under_another_key_is_rejected() {
+    let ours = ChallengeTokens::new(ChallengeKey::generate());
+    let theirs = ChallengeTokens::new(ChallengeKey::generate());
+    assert_eq!(ours.open(&theirs.seal(&challenge())).unwrap_err(), TokenError::UnknownKey);
+}
+
+#[test]
+fn test_rotation_keeps_previous_key_for_grace_period() {
+    let tokens = ChallengeTokens::new(ChallengeKey::generate());
+    let old = tokens.seal(&challenge());
+
+    tokens.rotate(ChallengeKey::generate(), Duration::from_secs(60));
+    let new = tokens.seal(&challenge());
+    assert!(tokens.open(&old).is_ok());
+    assert!(tokens.open(&new).is_ok());
+
+    // A second rotation with no grace retires the key that sealed `new` at once
+    // and drops the one that sealed `old` altogether
+    tokens.rotate(ChallengeKey::generate(), Duration::ZERO);
+    std::thread::sleep(Duration::from_millis(5));
+    assert_eq!(tokens.open(&old).unwrap_err(), TokenError::UnknownKey);
+    assert_eq!(tokens.open(&new).unwrap_err(), TokenError::UnknownKey);
+}
+
+#[test]
+fn test_redeem_refuses_a_second_use() {
+    let tokens = ChallengeTokens::new(ChallengeKey::generate());
+    let token = tokens.seal(&challenge());
+
+    assert!(tokens.redeem(&token).is_ok());
+    assert_eq!(tokens.redeem(&token).unwrap_err(), TokenError::Spent);
+    // Nothing is forgotten before it expires
+    assert_eq!(tokens.purge_spent(SystemTime::now()), 0);
+    assert_eq!(tokens.purge_spent(SystemTime::now() + Duration::from_secs(120)), 1);
+}
+
+#[test]
+fn test_load_key_file() {
+    let current = "11".repeat(32);
+    let previous = "22".repeat(32);
+    let sealed_before = ChallengeTokens::new(ChallengeKey::from_hex(&previous).unwrap()).seal(&challenge());
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "# current, then previous\n{}\n{}", current, previous).unwrap();
+    let tokens = ChallengeTokens::load(file.path(), Duration::from_secs(60)).unwrap();
+
+    assert_eq!(tokens.current_key_id(), ChallengeKey::from_hex(&current).unwrap().id());
+    assert!(tokens.open(&sealed_before).is_ok());
+
+    let mut short = tempfile::NamedTempFile::new().unwrap();
+    writeln!(short, "abcd").unwrap();
+    assert!(ChallengeTokens::load(short.path(), Duration::from_secs(60)).is_err());
+}
+
+#[test]
+fn test_previous_key_from_file_expires_after_grace() {
+    let previous = "22".repeat(32);
+    let sealed_before = ChallengeTokens::new(ChallengeKey::from_hex(&previous).unwrap()).seal(&challenge());
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "{}\n{}", "11".repeat(32), previous).unwrap();
+
+    let tokens = ChallengeTokens::load(file.path(), Duration::ZERO).unwrap();
+    std::thread::sleep(Duration::from_millis(5));
+
+    assert_eq!(tokens.open(&sealed_before).unwrap_err(), TokenError::UnknownKey);
+}
+
+#[test]
+fn test_reload_rotates_to_a_new_first_key() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "{}", "11".repeat(32)).unwrap();
+    let tokens = ChallengeTokens::load(file.path(), Duration::from_secs(60)).unwrap();
+    let old = tokens.seal(&challenge());
+    assert!(!tokens.reload(file.path(), Duration::from_secs(60)).unwrap());
+
+    let current = "33".repeat(32);
+    std::fs::write(file.path(), format!("{}\n{}\n", current, "11".repeat(32))).unwrap();
+
+    assert!(tokens.reload(file.path(), Duration::from_secs(60)).unwrap());
+    assert_eq!(tokens.current_key_id(), ChallengeKey::from_hex(&current).unwrap().id());
+    assert!(tokens.open(&old).is_ok());
+}
+
+#[tokio::test]
+async fn test_replicas_sharing_a_key_complete_each_others_logins() {
+    // Arrange: two replicas with their own session stores, sharing only users and the key
+    let key = ChallengeKey::generate();
+    let users: Arc<dyn UserStore> = Arc::new(MemoryUserStore::new());
+    let first_store = Arc::new(MemorySessionStore::new());
+    let group = test_params();
+    let first = AuthService::new(group.clone())
+        .with_user_store(users.clone())
+        .with_session_store(first_store.clone())
+        .with_challenge_tokens(Arc::new(ChallengeTokens::new(key.clone())));
+    let second = AuthService::new(group.clone())
+        .with_user_store(users)
+        .with_challenge_tokens(Arc::new(ChallengeTokens::new(key)));
+
+    let x = BigInt::from(6);
+    register(&first, &group, &x).await;
+
+    let k = BigInt::from(7);
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let challenge = first
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: r1.to_string(),
+            r2: r2.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let s = protocol::response(&group, &k, &challenge.c.parse().unwrap(), &x);
+    let answer = || {
+        Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_string(),
+            client_share: String::new(),
+        })
+    };
+
+    // Act
+    let login = second.verify_authentication(answer()).await;
+    let replay = second.verify_authentication(answer()).await;
+
+    // Assert: nothing was stored, and the replica that redeemed the token refuses it again
+    assert!(!login.unwrap().into_inner().session_id.is_empty());
+    assert_eq!(replay.unwrap_err().code(), tonic::Code::NotFound);
+    assert_eq!(first_store.purge_expired_challenges(SystemTime::now() + Duration::from_secs(3600)).unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_forged_and_expired_tokens_are_refused() {
+    // Arrange
+    let group = test_params();
+    let tokens = Arc::new(ChallengeTokens::new(ChallengeKey::generate()));
+    let service = AuthService::new(group.clone()).with_challenge_tokens(tokens.clone());
+    register(&service, &group, &BigInt::from(6)).await;
+
+    let mut expired = challenge();
+    expired.user = "test_user".to_string();
+    expired.expires_at = SystemTime::now() - Duration::from_secs(1);
+    let stranger = ChallengeTokens::new(ChallengeKey::generate()).seal(&challenge());
+
+    // Act
+    let forged = service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest { auth_id: stranger, s: "1".to_string(), client_share: String::new() }))
+        .await;
+    let stale = service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: tokens.seal(&expired),
+            s: "1".to_string(),
+            client_share: String::new(),
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(forged.unwrap_err().code(), tonic::Code::InvalidArgument);
+    assert_eq!(stale.unwrap_err().code(), tonic::Code::FailedPrecondition);
+}
@@ -0,0 +1,119 @@
+use pendersen_core::{protocol, Group, RistrettoParameters};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, LoginRequest};
+use pendersen_server::{AuthService, ChannelBinding, ConnectionInfo};
+
+use num_bigint::BigInt;
+use tonic::Request;
+
+mod common;
+use common::{now, register};
+
+type Scalar = <RistrettoParameters as Group>::Scalar;
+
+// What the victim's connection and the relaying attacker's connection export
+const VICTIM: ChannelBinding = ChannelBinding([1; 32]);
+const ATTACKER: ChannelBinding = ChannelBinding([2; 32]);
+
+// A login proof whose challenge is bound to `binding`, or plain without one
+fn login_request(group: &RistrettoParameters, x: &Scalar, binding: Option<&ChannelBinding>) -> LoginRequest {
+    let (y1, y2) = protocol::public_key(group, x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let timestamp = now();
+    let mut c = protocol::login_challenge(group, "test_user", (&y1, &y2), (&r1, &r2), &timestamp.to_be_bytes());
+    if let Some(binding) = binding {
+        c = protocol::bind_challenge(group, &c, &binding.0);
+    }
+    LoginRequest {
+        user: "test_user".to_string(),
+        r1: group.encode_element(&r1),
+        r2: group.encode_element(&r2),
+        s: group.encode_scalar(&protocol::response(group, &k, &c, x)),
+        timestamp,
+        client_share: String::new(),
+    }
+}
+
+// A request as it would arrive on a TLS connection with exporter value `binding`
+fn on_connection<T>(message: T, binding: &ChannelBinding) -> Request<T> {
+    let mut request = Request::new(message);
+    request.extensions_mut().insert(ConnectionInfo {
+        remote_addr: None,
+        channel_binding: binding.clone(),
+    });
+    request
+}
+
+// Commit, fetch a challenge on the attacker's connection and return the victim's
+// answer to it, computed for the victim's own connection
+async fn relayed_answer(auth_service: &AuthService<RistrettoParameters>, group: &RistrettoParameters, x: &Scalar) -> AuthenticationAnswerRequest {
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let challenge = AuthenticationChallengeRequest {
+        user: "test_user".to_string(),
+        r1: group.encode_element(&r1),
+        r2: group.encode_element(&r2),
+    };
+    let response = auth_service
+        .create_authentication_challenge(on_connection(challenge, &ATTACKER))
+        .await
+        .unwrap()
+        .into_inner();
+    let c = group.decode_scalar(&response.c).unwrap();
+    let s = protocol::response(group, &k, &protocol::bind_challenge(group, &c, &VICTIM.0), x);
+    AuthenticationAnswerRequest {
+        auth_id: response.auth_id,
+        s: group.encode_scalar(&s),
+        client_share: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_relayed_interactive_answer_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let answer = relayed_answer(&auth_service, &group, &x).await;
+
+    // Act: the attacker submits the victim's answer on its own connection
+    let status = auth_service
+        .verify_authentication(on_connection(answer, &ATTACKER))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+    // The same answer is accepted on the connection it was computed for
+    let answer = relayed_answer(&auth_service, &group, &x).await;
+    auth_service.verify_authentication(on_connection(answer, &VICTIM)).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_relayed_login_proof_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+
+    // Act
+    let relayed = auth_service
+        .login(on_connection(login_request(&group, &x, Some(&VICTIM)), &ATTACKER))
+        .await;
+    // A proof that ignores the channel binding fails on any TLS connection
+    let unbound = auth_service
+        .login(on_connection(login_request(&group, &x, None), &ATTACKER))
+        .await;
+
+    // Assert
+    assert_eq!(relayed.unwrap_err().code(), tonic::Code::Unauthenticated);
+    assert_eq!(unbound.unwrap_err().code(), tonic::Code::Unauthenticated);
+    auth_service
+        .login(on_connection(login_request(&group, &x, Some(&VICTIM)), &VICTIM))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,60 @@
+// Fixtures shared by the integration tests; each test binary uses some of them
+#![allow(dead_code)]
+
+use pendersen_core::{protocol, Group, GroupParameters, KdfParameters};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{LoginRequest, RegisterRequest};
+use pendersen_server::AuthService;
+
+use num_bigint::BigInt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tonic::Request;
+
+// p = 23 = 2 * 11 + 1, g and h generate the subgroup of order 11
+pub fn test_params() -> GroupParameters {
+    GroupParameters::new(BigInt::from(23), BigInt::from(11), BigInt::from(4), BigInt::from(9))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+// Registration of test_user with the key for x and a proof of knowledge of x
+pub fn register_request<G: Group>(group: &G, x: &G::Scalar, kdf: Option<KdfParameters>) -> RegisterRequest {
+    let (y1, y2) = protocol::public_key(group, x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let c = protocol::registration_challenge(group, "test_user", (&y1, &y2), (&r1, &r2), kdf.as_ref());
+    RegisterRequest {
+        user: "test_user".to_string(),
+        y1: group.encode_element(&y1),
+        y2: group.encode_element(&y2),
+        kdf: kdf.map(Into::into),
+        r1: group.encode_element(&r1),
+        r2: group.encode_element(&r2),
+        s: group.encode_scalar(&protocol::response(group, &k, &c, x)),
+    }
+}
+
+pub async fn register<G: Group>(auth_service: &AuthService<G>, group: &G, x: &G::Scalar) {
+    auth_service
+        .register(Request::new(register_request(group, x, None)))
+        .await
+        .unwrap();
+}
+
+// Non-interactive login of test_user, registered with x, proving knowledge of `password`
+pub fn login_request<G: Group>(group: &G, x: &G::Scalar, password: &G::Scalar, timestamp: u64) -> LoginRequest {
+    let (y1, y2) = protocol::public_key(group, x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let c = protocol::login_challenge(group, "test_user", (&y1, &y2), (&r1, &r2), &timestamp.to_be_bytes());
+    LoginRequest {
+        user: "test_user".to_string(),
+        r1: group.encode_element(&r1),
+        r2: group.encode_element(&r2),
+        s: group.encode_scalar(&protocol::response(group, &k, &c, password)),
+        timestamp,
+        client_share: String::new(),
+    }
+}
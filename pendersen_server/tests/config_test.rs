@@ -0,0 +1,202 @@
+use pendersen_server::config::TlsConfig;
+use pendersen_server::{Invocation, RateLimit, ServerConfig};
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::time::Duration;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn no_env(_: &str) -> Option<String> {
+    None
+}
+
+fn config_file(contents: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn test_defaults_match_the_built_in_constants() {
+    let invocation = Invocation::parse(Vec::new(), no_env).unwrap();
+
+    assert_eq!(invocation.config, ServerConfig::default());
+    assert!(!invocation.check_config);
+    let config = invocation.config;
+    assert_eq!(config.listen_addr().unwrap().to_string(), "[::1]:50051");
+    assert_eq!(config.session_ttl(), Duration::from_secs(3600));
+    assert_eq!(config.lockout_policy(), Default::default());
+    assert_eq!(config.rate_limit_policy(), Default::default());
+}
+
+#[test]
+fn test_config_file_is_read() {
+    let file = config_file(
+        r#"
+        listen = "127.0.0.1:6000"
+        parameters = "ffdhe3072"
+        user_store = "sqlite:users.db"
+
+        [ttl]
+        session_secs = 600
+
+        [rate_limit.methods.GetKdfParameters]
+        burst = 2
+        per_second = 0.5
+        "#,
+    );
+
+    let invocation = Invocation::parse(args(&["--config", file.path().to_str().unwrap()]), no_env).unwrap();
+
+    let config = invocation.config;
+    assert_eq!(config.listen, "127.0.0.1:6000");
+    assert_eq!(config.parameters, "ffdhe3072");
+    assert_eq!(config.user_store, "sqlite:users.db");
+    assert_eq!(config.session_ttl(), Duration::from_secs(600));
+    // Unset values keep their defaults
+    assert_eq!(config.challenge_ttl(), Duration::from_secs(60));
+    // Listing one method keeps the limits on the proof methods
+    let policy = config.rate_limit_policy();
+    assert_eq!(policy.methods["GetKdfParameters"], RateLimit { burst: 2, per_second: 0.5 });
+    assert_eq!(policy.methods["Login"], RateLimit { burst: 5, per_second: 1.0 });
+}
+
+#[test]
+fn test_flags_beat_environment_beats_file() {
+    let file = config_file("listen = \"127.0.0.1:6000\"\nuser_store = \"json:file.json\"\nsession_store = \"sqlite:file.db\"\n");
+    let env: HashMap<&str, &str> = [
+        ("PENDERSEN_CONFIG", file.path().to_str().unwrap()),
+        ("PENDERSEN_USER_STORE", "json:env.json"),
+        ("PENDERSEN_LISTEN", "127.0.0.1:7000"),
+    ]
+    .into_iter()
+    .collect();
+
+    let invocation = Invocation::parse(args(&["--listen", "127.0.0.1:8000"]), |var| env.get(var).map(|value| value.to_string())).unwrap();
+
+    let config = invocation.config;
+    assert_eq!(config.listen, "127.0.0.1:8000");
+    assert_eq!(config.user_store, "json:env.json");
+    assert_eq!(config.session_store, "sqlite:file.db");
+}
+
+#[test]
+fn test_positional_arguments_are_still_accepted() {
+    let invocation = Invocation::parse(args(&["ristretto255", "sqlite:users.db", "sqlite:sessions.db"]), no_env).unwrap();
+
+    assert_eq!(invocation.config.parameters, "ristretto255");
+    assert_eq!(invocation.config.user_store, "sqlite:users.db");
+    assert_eq!(invocation.config.session_store, "sqlite:sessions.db");
+}
+
+#[test]
+fn test_rate_limit_and_lockout_flags() {
+    let invocation = Invocation::parse(
+        args(&[
+            "--rate-limit-allow",
+            "10.0.0.1, ::1",
+            "--rate-limit-burst",
+            "3",
+            "--lockout-threshold",
+            "0",
+            "--check-config",
+        ]),
+        no_env,
+    )
+    .unwrap();
+
+    assert!(invocation.check_config);
+    let policy = invocation.config.rate_limit_policy();
+    assert!(policy.allowlist.contains(&"10.0.0.1".parse::<IpAddr>().unwrap()));
+    assert!(policy.allowlist.contains(&"::1".parse::<IpAddr>().unwrap()));
+    assert_eq!(policy.default.unwrap().burst, 3);
+    assert_eq!(invocation.config.lockout_policy().delay(100), None);
+
+    let invocation = Invocation::parse(args(&["--rate-limit", "off"]), no_env).unwrap();
+    assert!(invocation.config.rate_limit_policy().default.is_none());
+}
+
+#[test]
+fn test_invalid_configuration_is_rejected() {
+    let cases: &[&[&str]] = &[
+        &["--listen", "nowhere"],
+        &["--user-store", "postgres:db"],
+        &["--session-ttl", "soon"],
+        &["--session-ttl", "0"],
+        &["--log-level", "chatty"],
+        &["--rate-limit-per-second", "0"],
+        &["--rate-limit-per-second", "1e-300"],
+        &["--lockout-base-delay", "1000", "--lockout-max-delay", "10"],
+        &["--tls-cert", "server.pem"],
+        &["--no-such-flag", "x"],
+        &["--listen"],
+    ];
+    for case in cases {
+        assert!(Invocation::parse(args(case), no_env).is_err(), "{:?} was accepted", case);
+    }
+}
+
+#[test]
+fn test_unknown_keys_in_file_are_rejected() {
+    let error = ServerConfig::from_toml("listne = \"127.0.0.1:6000\"\n").unwrap_err();
+
+    assert!(error.contains("listne"), "{}", error);
+}
+
+#[test]
+fn test_limits_for_unknown_rpcs_are_rejected() {
+    let config = ServerConfig::from_toml("[rate_limit.methods.Logn]\nburst = 1\nper_second = 1.0\n").unwrap();
+
+    let error = config.validate().unwrap_err();
+
+    assert!(error.contains("Logn"), "{}", error);
+}
+
+#[test]
+fn test_effective_configuration_round_trips_through_toml() {
+    let invocation = Invocation::parse(
+        args(&["--tls-cert", "server.pem", "--tls-key", "server.key", "--challenge-keys", "challenge.keys"]),
+        no_env,
+    )
+    .unwrap();
+
+    let printed = invocation.config.to_toml();
+
+    assert_eq!(ServerConfig::from_toml(&printed).unwrap(), invocation.config);
+}
+
+#[test]
+fn test_tls_files_must_be_pem() {
+    let not_pem = config_file("certificate\n");
+    let tls = TlsConfig {
+        cert: not_pem.path().to_path_buf(),
+        key: not_pem.path().to_path_buf(),
+        client_ca: None,
+    };
+
+    let error = tls.load().err().unwrap();
+    assert!(error.to_string().contains("not a PEM file"), "{}", error);
+
+    let missing = TlsConfig {
+        cert: "/nonexistent/server.pem".into(),
+        ..tls
+    };
+    assert!(missing.load().err().unwrap().to_string().contains("/nonexistent/server.pem"));
+}
+
+#[test]
+fn test_server_key_seed_is_read_from_hex() {
+    let file = config_file(&format!("{}\n", "ab".repeat(32)));
+    let short = config_file("abcd\n");
+    let flag = |path: &std::path::Path| {
+        Invocation::parse(args(&["--server-key", path.to_str().unwrap()]), no_env).unwrap().config
+    };
+
+    assert_eq!(ServerConfig::default().server_key_seed(), Ok(None));
+    assert_eq!(flag(file.path()).server_key_seed(), Ok(Some([0xab; 32])));
+    assert!(flag(short.path()).server_key_seed().unwrap_err().contains("32 bytes"));
+}
@@ -0,0 +1,207 @@
+use pendersen_core::key_exchange::{bind_key_share, session_key};
+use pendersen_core::{protocol, Group, KeyShare, RistrettoParameters, ServerKey};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{AuthenticationAnswerRequest, AuthenticationChallengeRequest};
+use pendersen_server::session_registry::DEFAULT_SESSION_TTL;
+use pendersen_server::{AuthService, ChannelBinding, ConnectionInfo, MemorySessionStore, SessionRegistry, SessionStore};
+
+use num_bigint::BigInt;
+use std::sync::Arc;
+use tonic::Request;
+
+mod common;
+use common::register;
+
+type Element = <RistrettoParameters as Group>::Element;
+type Scalar = <RistrettoParameters as Group>::Scalar;
+
+// Commit and fetch a challenge, returning the nonce, commitment, auth_id and c
+async fn challenge(
+    auth_service: &AuthService<RistrettoParameters>,
+    group: &RistrettoParameters,
+) -> (Scalar, (Element, Element), String, Scalar) {
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(group, &k);
+    let response = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let c = group.decode_scalar(&response.c).unwrap();
+    (k, (r1, r2), response.auth_id, c)
+}
+
+#[tokio::test]
+async fn test_key_exchange_stores_the_key_the_client_derives() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let sessions: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::new());
+    let auth_service = AuthService::new(group.clone())
+        .with_session_store(sessions.clone())
+        .with_server_key(ServerKey::generate(&group));
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, (r1, r2), auth_id, c) = challenge(&auth_service, &group).await;
+    let share = KeyShare::generate(&group);
+    let c = bind_key_share(&group, &c, share.public());
+
+    // Act
+    let response = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id,
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            client_share: group.encode_element(share.public()),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    let server_share = group.decode_element(&response.server_share).unwrap();
+    let shared = share.agree(&group, &server_share).unwrap();
+    let key = session_key(&group, "test_user", (&r1, &r2), &c, (share.public(), &server_share), &shared);
+    let session = SessionRegistry::new(sessions, DEFAULT_SESSION_TTL)
+        .validate(&response.session_id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.key, Some(key));
+}
+
+#[tokio::test]
+async fn test_plain_login_has_no_key() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let sessions: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::new());
+    let auth_service = AuthService::new(group.clone()).with_session_store(sessions.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, _, auth_id, c) = challenge(&auth_service, &group).await;
+
+    // Act
+    let response = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id,
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    assert!(response.server_share.is_empty());
+    let registry = SessionRegistry::new(sessions, DEFAULT_SESSION_TTL);
+    assert_eq!(registry.validate(&response.session_id).unwrap().unwrap().key, None);
+}
+
+#[tokio::test]
+async fn test_swapped_client_share_is_rejected() {
+    // Arrange: someone in the middle replaces the client's share with their own
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone()).with_server_key(ServerKey::generate(&group));
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, _, auth_id, c) = challenge(&auth_service, &group).await;
+    let share = KeyShare::generate(&group);
+    let c = bind_key_share(&group, &c, share.public());
+    let substitute = KeyShare::generate(&group);
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id,
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            client_share: group.encode_element(substitute.public()),
+        }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_identity_client_share_is_rejected() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone()).with_server_key(ServerKey::generate(&group));
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, _, auth_id, c) = challenge(&auth_service, &group).await;
+    let identity = group.identity();
+    let c = bind_key_share(&group, &c, &identity);
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id,
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            client_share: group.encode_element(&identity),
+        }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    assert!(status.message().contains("client_share"), "{}", status.message());
+}
+
+#[tokio::test]
+async fn test_key_exchange_is_refused_without_tls_or_a_server_key() {
+    // Arrange: nothing would let the client tell our share from an attacker's
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, _, auth_id, c) = challenge(&auth_service, &group).await;
+    let share = KeyShare::generate(&group);
+    let c = bind_key_share(&group, &c, share.public());
+
+    // Act
+    let status = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id,
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            client_share: group.encode_element(share.public()),
+        }))
+        .await
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn test_key_exchange_runs_over_tls_without_a_server_key() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let sessions: Arc<dyn SessionStore> = Arc::new(MemorySessionStore::new());
+    let auth_service = AuthService::new(group.clone()).with_session_store(sessions.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (k, _, auth_id, c) = challenge(&auth_service, &group).await;
+    let share = KeyShare::generate(&group);
+    let binding = ChannelBinding([7; 32]);
+    let c = protocol::bind_challenge(&group, &bind_key_share(&group, &c, share.public()), &binding.0);
+    let mut request = Request::new(AuthenticationAnswerRequest {
+        auth_id,
+        s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+        client_share: group.encode_element(share.public()),
+    });
+    request.extensions_mut().insert(ConnectionInfo {
+        remote_addr: None,
+        channel_binding: binding,
+    });
+
+    // Act
+    let response = auth_service.verify_authentication(request).await.unwrap().into_inner();
+
+    // Assert
+    assert!(!response.server_share.is_empty());
+    let registry = SessionRegistry::new(sessions, DEFAULT_SESSION_TTL);
+    assert!(registry.validate(&response.session_id).unwrap().unwrap().key.is_some());
+}
@@ -0,0 +1,152 @@
+use pendersen_core::{protocol, Group, RistrettoParameters};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::AuthenticationChallengeRequest;
+use pendersen_server::{AttemptTracker, AuthService, LockoutPolicy};
+
+use num_bigint::BigInt;
+use std::time::{Duration, SystemTime};
+use tonic::Request;
+
+mod common;
+use common::{login_request, now, register};
+
+fn policy() -> LockoutPolicy {
+    LockoutPolicy {
+        threshold: 3,
+        base_delay: Duration::from_secs(10),
+        max_delay: Duration::from_secs(60),
+    }
+}
+
+#[test]
+fn test_delay_doubles_after_threshold() {
+    let policy = policy();
+
+    assert_eq!(policy.delay(2), None);
+    assert_eq!(policy.delay(3), Some(Duration::from_secs(10)));
+    assert_eq!(policy.delay(4), Some(Duration::from_secs(20)));
+    assert_eq!(policy.delay(5), Some(Duration::from_secs(40)));
+    // Capped at max_delay, however many failures pile up
+    assert_eq!(policy.delay(6), Some(Duration::from_secs(60)));
+    assert_eq!(policy.delay(u32::MAX), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn test_zero_threshold_disables_lockout() {
+    let policy = LockoutPolicy { threshold: 0, ..policy() };
+
+    assert_eq!(policy.delay(100), None);
+}
+
+#[test]
+fn test_tracker_locks_out_until_delay_passes() {
+    let tracker = AttemptTracker::new(policy());
+    let start = SystemTime::now();
+
+    assert_eq!(tracker.record_failure("alice", start), None);
+    assert_eq!(tracker.record_failure("alice", start), None);
+    assert!(tracker.check("alice", start).is_ok());
+    assert_eq!(tracker.record_failure("alice", start), Some(Duration::from_secs(10)));
+
+    assert_eq!(tracker.check("alice", start + Duration::from_secs(4)), Err(Duration::from_secs(6)));
+    assert!(tracker.check("alice", start + Duration::from_secs(10)).is_ok());
+    // Other users are unaffected
+    assert!(tracker.check("bob", start).is_ok());
+}
+
+#[test]
+fn test_success_resets_failures() {
+    let tracker = AttemptTracker::new(policy());
+    let now = SystemTime::now();
+    tracker.record_failure("alice", now);
+    tracker.record_failure("alice", now);
+
+    tracker.record_success("alice");
+
+    assert_eq!(tracker.failures("alice"), 0);
+    assert_eq!(tracker.record_failure("alice", now), None);
+}
+
+#[test]
+fn test_attempts_in_flight_count_toward_lockout() {
+    let tracker = AttemptTracker::new(policy());
+    let now = SystemTime::now();
+
+    // Three guesses started before any finished use up the threshold
+    for _ in 0..3 {
+        assert!(tracker.begin_attempt("alice", now).is_ok());
+    }
+
+    assert_eq!(tracker.begin_attempt("alice", now), Err(Duration::from_secs(10)));
+    tracker.record_success("alice");
+    assert!(tracker.begin_attempt("alice", now).is_ok());
+}
+
+#[test]
+fn test_failures_are_forgotten_after_quiet_period() {
+    let tracker = AttemptTracker::new(policy());
+    let start = SystemTime::now();
+    for _ in 0..3 {
+        tracker.record_failure("alice", start);
+    }
+
+    let later = start + Duration::from_secs(60);
+    assert_eq!(tracker.purge(later), 1);
+    assert_eq!(tracker.failures("alice"), 0);
+    assert_eq!(tracker.record_failure("alice", later), None);
+}
+
+#[tokio::test]
+async fn test_repeated_failures_lock_out_user() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone()).with_lockout_policy(policy());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let wrong = group.scalar_from_bigint(&BigInt::from(41));
+    register(&auth_service, &group, &x).await;
+    for _ in 0..3 {
+        let status = auth_service.login(Request::new(login_request(&group, &x, &wrong, now()))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    // Act: even the right password is refused while locked out
+    let status = auth_service.login(Request::new(login_request(&group, &x, &x, now()))).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "10");
+
+    let (r1, r2) = protocol::commitment(&group, &group.random_scalar());
+    let status = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn test_successful_login_resets_failures() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone()).with_lockout_policy(policy());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let wrong = group.scalar_from_bigint(&BigInt::from(41));
+    register(&auth_service, &group, &x).await;
+    for _ in 0..2 {
+        auth_service.login(Request::new(login_request(&group, &x, &wrong, now()))).await.unwrap_err();
+    }
+    auth_service.login(Request::new(login_request(&group, &x, &x, now()))).await.unwrap();
+
+    // Act: two more failures stay under the threshold of three
+    for _ in 0..2 {
+        let status = auth_service.login(Request::new(login_request(&group, &x, &wrong, now()))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    // Assert
+    auth_service.login(Request::new(login_request(&group, &x, &x, now()))).await.unwrap();
+}
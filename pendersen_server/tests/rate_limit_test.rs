@@ -0,0 +1,214 @@
+use pendersen_core::RistrettoParameters;
+use pendersen_server::pb::auth_client::AuthClient;
+use pendersen_server::pb::auth_server::AuthServer;
+use pendersen_server::pb::{KdfParametersRequest, ValidateSessionRequest};
+use pendersen_server::config::TlsConfig;
+use pendersen_server::{tls_incoming, AuthService, RateLimit, RateLimitLayer, RateLimitPolicy, RateLimiter};
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Endpoint, Server, Uri};
+
+const ALICE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+const BOB: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+fn policy() -> RateLimitPolicy {
+    RateLimitPolicy::unlimited().with_method("Login", RateLimit { burst: 2, per_second: 0.5 })
+}
+
+#[test]
+fn test_bucket_allows_burst_then_refills() {
+    let limiter = RateLimiter::new(policy());
+    let start = Instant::now();
+
+    assert!(limiter.check(ALICE, "Login", start).is_ok());
+    assert!(limiter.check(ALICE, "Login", start).is_ok());
+    assert_eq!(limiter.check(ALICE, "Login", start), Err(Duration::from_secs(2)));
+
+    // Half a token after one second, a whole one after two
+    assert_eq!(limiter.check(ALICE, "Login", start + Duration::from_secs(1)), Err(Duration::from_secs(1)));
+    assert!(limiter.check(ALICE, "Login", start + Duration::from_secs(2)).is_ok());
+}
+
+#[test]
+fn test_limits_are_per_address_and_method() {
+    let limiter = RateLimiter::new(policy().with_default(Some(RateLimit { burst: 1, per_second: 1.0 })));
+    let now = Instant::now();
+    limiter.check(ALICE, "Login", now).unwrap();
+    limiter.check(ALICE, "Login", now).unwrap();
+
+    assert!(limiter.check(ALICE, "Login", now).is_err());
+    assert!(limiter.check(BOB, "Login", now).is_ok());
+    // Methods without their own limit fall back to the default
+    assert!(limiter.check(ALICE, "Register", now).is_ok());
+    assert!(limiter.check(ALICE, "Register", now).is_err());
+}
+
+#[test]
+fn test_allowlisted_addresses_are_not_limited() {
+    let limiter = RateLimiter::new(policy().allow(ALICE));
+    let now = Instant::now();
+
+    for _ in 0..10 {
+        assert!(limiter.check(ALICE, "Login", now).is_ok());
+    }
+    // IPv4-mapped IPv6 addresses match their IPv4 entry
+    let mapped = IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped());
+    assert!(limiter.check(mapped, "Login", now).is_ok());
+}
+
+#[test]
+fn test_unknown_methods_share_one_bucket() {
+    let limiter = RateLimiter::new(RateLimitPolicy::unlimited().with_default(Some(RateLimit { burst: 1, per_second: 1.0 })));
+    let now = Instant::now();
+    limiter.check(ALICE, "NoSuchMethod", now).unwrap();
+
+    assert!(limiter.check(ALICE, "AnotherMadeUpMethod", now).is_err());
+    assert!(limiter.check(ALICE, "Login", now).is_ok());
+}
+
+#[test]
+fn test_tiny_refill_rate_waits_without_overflowing() {
+    let limiter = RateLimiter::new(RateLimitPolicy::unlimited().with_method("Login", RateLimit { burst: 1, per_second: 1e-300 }));
+    let now = Instant::now();
+    limiter.check(ALICE, "Login", now).unwrap();
+
+    assert_eq!(limiter.check(ALICE, "Login", now), Err(Duration::MAX));
+}
+
+#[test]
+fn test_purge_drops_refilled_buckets() {
+    let limiter = RateLimiter::new(policy());
+    let start = Instant::now();
+    limiter.check(ALICE, "Login", start).unwrap();
+    limiter.check(BOB, "Login", start + Duration::from_secs(2)).unwrap();
+
+    // Alice's bucket is full again after two seconds, Bob's is not
+    assert_eq!(limiter.purge(start + Duration::from_secs(3)), 1);
+}
+
+// Serve an AuthService behind the rate limit layer on a loopback port
+async fn serve(policy: RateLimitPolicy) -> AuthClient<Channel> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let auth_service = AuthService::new(RistrettoParameters::default());
+    tokio::spawn(
+        Server::builder()
+            .layer(RateLimitLayer::new(policy))
+            .add_service(AuthServer::new(auth_service))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    AuthClient::connect(format!("http://{}", addr)).await.unwrap()
+}
+
+// Serve the same behind TLS, with a certificate for localhost from a fresh CA,
+// and connect a client that trusts that CA
+async fn serve_tls(policy: RateLimitPolicy) -> AuthClient<Channel> {
+    let dir = TempDir::new().unwrap();
+    let mut params = CertificateParams::new(Vec::new());
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = Certificate::from_params(params).unwrap();
+    let cert = Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+    let tls = TlsConfig {
+        cert: dir.path().join("server.pem"),
+        key: dir.path().join("server.key"),
+        client_ca: None,
+    };
+    fs::write(&tls.cert, cert.serialize_pem_with_signer(&ca).unwrap()).unwrap();
+    fs::write(&tls.key, cert.serialize_private_key_pem()).unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let incoming = tls_incoming(listener, tls.load().unwrap());
+    let auth_service = AuthService::new(RistrettoParameters::default());
+    tokio::spawn(
+        Server::builder()
+            .layer(RateLimitLayer::new(policy))
+            .add_service(AuthServer::new(auth_service))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut config = ClientConfig::new();
+    config.root_store.add_pem_file(&mut ca.serialize_pem().unwrap().as_bytes()).unwrap();
+    config.set_protocols(&[b"h2".to_vec()]);
+    let connector = TlsConnector::from(Arc::new(config));
+    let channel = Endpoint::from_shared(format!("https://localhost:{}", addr.port()))
+        .unwrap()
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let connector = connector.clone();
+            async move {
+                let stream = TcpStream::connect(addr).await?;
+                connector.connect(DNSNameRef::try_from_ascii_str("localhost").unwrap(), stream).await
+            }
+        }))
+        .await
+        .unwrap();
+    AuthClient::new(channel)
+}
+
+fn kdf_request() -> KdfParametersRequest {
+    KdfParametersRequest { user: "nobody".to_string() }
+}
+
+#[tokio::test]
+async fn test_layer_rejects_requests_over_the_limit() {
+    // Arrange
+    let policy = RateLimitPolicy::unlimited().with_method("GetKdfParameters", RateLimit { burst: 2, per_second: 0.5 });
+    let mut client = serve(policy).await;
+    for _ in 0..2 {
+        let status = client.get_kdf_parameters(kdf_request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    // Act
+    let status = client.get_kdf_parameters(kdf_request()).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+    // Other methods keep their own budget
+    let status = client
+        .validate_session(ValidateSessionRequest { session_id: "nope".to_string() })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_layer_skips_allowlisted_clients() {
+    // Arrange
+    let policy = RateLimitPolicy::unlimited()
+        .with_method("GetKdfParameters", RateLimit { burst: 1, per_second: 0.5 })
+        .allow(IpAddr::V4(Ipv4Addr::LOCALHOST));
+    let mut client = serve(policy).await;
+
+    // Act and assert
+    for _ in 0..5 {
+        let status = client.get_kdf_parameters(kdf_request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+}
+
+#[tokio::test]
+async fn test_layer_limits_clients_over_tls() {
+    // Arrange
+    let policy = RateLimitPolicy::unlimited().with_method("GetKdfParameters", RateLimit { burst: 1, per_second: 0.5 });
+    let mut client = serve_tls(policy).await;
+    let status = client.get_kdf_parameters(kdf_request()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    // Act
+    let status = client.get_kdf_parameters(kdf_request()).await.unwrap_err();
+
+    // Assert
+    assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+}
@@ -0,0 +1,146 @@
+use pendersen_core::key_exchange::bind_key_share;
+use pendersen_core::server_key::{verify, LoginRecord};
+use pendersen_core::{protocol, Group, KeyShare, RistrettoParameters, ServerKey, ServerProof};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{self, AuthenticationAnswerRequest, AuthenticationChallengeRequest, LoginRequest};
+use pendersen_server::AuthService;
+
+use num_bigint::BigInt;
+use tonic::Request;
+
+mod common;
+use common::{now, register};
+
+fn decode_proof(group: &RistrettoParameters, proof: pb::ServerProof) -> ServerProof<RistrettoParameters> {
+    ServerProof {
+        r: group.decode_element(&proof.r).unwrap(),
+        s: group.decode_scalar(&proof.s).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn test_interactive_login_carries_a_server_proof() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let server_key = ServerKey::generate(&group);
+    let public = *server_key.public();
+    let auth_service = AuthService::new(group.clone()).with_server_key(server_key);
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let challenge = auth_service
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let c = group.decode_scalar(&challenge.c).unwrap();
+    let s = protocol::response(&group, &k, &c, &x);
+
+    // Act
+    let response = auth_service
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id,
+            s: group.encode_scalar(&s),
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    let login = LoginRecord {
+        user: "test_user",
+        commitment: (&r1, &r2),
+        challenge: &c,
+        response: &s,
+        key_shares: None,
+        session_id: &response.session_id,
+        expires_at: response.expires_at,
+    };
+    let proof = decode_proof(&group, response.server_proof.unwrap());
+    assert!(verify(&group, &public, &login, &proof));
+    assert!(!verify(&group, ServerKey::generate(&group).public(), &login, &proof));
+}
+
+#[tokio::test]
+async fn test_login_proof_covers_the_key_exchange() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let server_key = ServerKey::generate(&group);
+    let public = *server_key.public();
+    let auth_service = AuthService::new(group.clone()).with_server_key(server_key);
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (y1, y2) = protocol::public_key(&group, &x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let timestamp = now();
+    let share = KeyShare::generate(&group);
+    let c = protocol::login_challenge(&group, "test_user", (&y1, &y2), (&r1, &r2), &timestamp.to_be_bytes());
+    let c = bind_key_share(&group, &c, share.public());
+    let s = protocol::response(&group, &k, &c, &x);
+
+    // Act
+    let response = auth_service
+        .login(Request::new(LoginRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+            s: group.encode_scalar(&s),
+            timestamp,
+            client_share: group.encode_element(share.public()),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    let server_share = group.decode_element(&response.server_share).unwrap();
+    let login = LoginRecord {
+        user: "test_user",
+        commitment: (&r1, &r2),
+        challenge: &c,
+        response: &s,
+        key_shares: Some((share.public(), &server_share)),
+        session_id: &response.session_id,
+        expires_at: response.expires_at,
+    };
+    let proof = decode_proof(&group, response.server_proof.unwrap());
+    assert!(verify(&group, &public, &login, &proof));
+}
+
+#[tokio::test]
+async fn test_server_without_a_key_sends_no_proof() {
+    // Arrange
+    let group = RistrettoParameters::default();
+    let auth_service = AuthService::new(group.clone());
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    register(&auth_service, &group, &x).await;
+    let (y1, y2) = protocol::public_key(&group, &x);
+    let k = group.random_scalar();
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let timestamp = now();
+    let c = protocol::login_challenge(&group, "test_user", (&y1, &y2), (&r1, &r2), &timestamp.to_be_bytes());
+
+    // Act
+    let response = auth_service
+        .login(Request::new(LoginRequest {
+            user: "test_user".to_string(),
+            r1: group.encode_element(&r1),
+            r2: group.encode_element(&r2),
+            s: group.encode_scalar(&protocol::response(&group, &k, &c, &x)),
+            timestamp,
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+
+    // Assert
+    assert!(response.server_proof.is_none());
+}
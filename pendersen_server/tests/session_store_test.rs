@@ -0,0 +1,210 @@
+use pendersen_core::{protocol, SessionKey};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, ValidateSessionRequest};
+use pendersen_server::{
+    AuthService, MemorySessionStore, MemoryUserStore, Session, SessionRegistry, SessionStore, SessionStoreConfig, SqliteSessionStore,
+    StoredChallenge, UserStore,
+};
+
+use num_bigint::BigInt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tonic::Request;
+
+mod common;
+use common::{register, test_params};
+
+fn challenge(expires_at: SystemTime) -> StoredChallenge {
+    StoredChallenge {
+        user: "alice".to_string(),
+        r1: "3".to_string(),
+        r2: "13".to_string(),
+        c: "5".to_string(),
+        expires_at,
+    }
+}
+
+fn session(expires_at: SystemTime) -> Session {
+    Session {
+        user: "alice".to_string(),
+        expires_at,
+        key: None,
+    }
+}
+
+// The behaviour every implementation must share
+fn check_contract(store: &dyn SessionStore) {
+    let now = SystemTime::now();
+    // SQLite keeps millisecond precision
+    let later = now + Duration::from_secs(60);
+    let later = SystemTime::UNIX_EPOCH + Duration::from_millis(later.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64);
+    let earlier = now - Duration::from_secs(60);
+
+    // Challenges are taken exactly once, expired or not
+    store.put_challenge("live", &challenge(later)).unwrap();
+    store.put_challenge("stale", &challenge(earlier)).unwrap();
+    assert_eq!(store.take_challenge("live").unwrap(), Some(challenge(later)));
+    assert_eq!(store.take_challenge("live").unwrap(), None);
+    assert!(store.take_challenge("stale").unwrap().unwrap().expires_at <= now);
+
+    store.put_challenge("a", &challenge(earlier)).unwrap();
+    store.put_challenge("b", &challenge(earlier)).unwrap();
+    store.put_challenge("c", &challenge(later)).unwrap();
+    assert_eq!(store.purge_expired_challenges(now).unwrap(), 2);
+    assert!(store.take_challenge("c").unwrap().is_some());
+
+    // A login proof is accepted once until it expires, then may be recorded again
+    assert!(store.record_proof("used", later).unwrap());
+    assert!(!store.record_proof("used", later).unwrap());
+    assert!(store.record_proof("lapsed", earlier).unwrap());
+    assert!(store.record_proof("lapsed", later).unwrap());
+    assert!(store.record_proof("old", earlier).unwrap());
+    assert_eq!(store.purge_expired_proofs(now).unwrap(), 1);
+
+    // Expired sessions are invisible and purged
+    store.put_session("fresh", &session(later)).unwrap();
+    store.put_session("old", &session(earlier)).unwrap();
+    assert_eq!(store.get_session("fresh").unwrap(), Some(session(later)));
+    assert_eq!(store.get_session("old").unwrap(), None);
+    assert_eq!(store.purge_expired_sessions(now).unwrap(), 1);
+    assert!(store.revoke_session("fresh").unwrap());
+    assert!(!store.revoke_session("fresh").unwrap());
+    assert_eq!(store.get_session("fresh").unwrap(), None);
+
+    // A key agreed during login is kept with the session
+    let keyed = Session {
+        key: Some(SessionKey([7; 32])),
+        ..session(later)
+    };
+    store.put_session("keyed", &keyed).unwrap();
+    assert_eq!(store.get_session("keyed").unwrap(), Some(keyed));
+}
+
+#[test]
+fn test_memory_session_store_contract() {
+    check_contract(&MemorySessionStore::new());
+}
+
+#[test]
+fn test_sqlite_session_store_contract() {
+    check_contract(&SqliteSessionStore::in_memory().unwrap());
+}
+
+#[test]
+fn test_session_store_config_parsing() {
+    assert_eq!(SessionStoreConfig::parse("memory").unwrap(), SessionStoreConfig::Memory);
+    assert_eq!(
+        SessionStoreConfig::parse("sqlite:/run/pendersen/sessions.db").unwrap(),
+        SessionStoreConfig::Sqlite(PathBuf::from("/run/pendersen/sessions.db"))
+    );
+    assert!(SessionStoreConfig::parse("json:sessions.json").is_err());
+}
+
+#[test]
+fn test_sqlite_challenge_is_taken_once_across_connections() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sessions.db");
+    let first = SqliteSessionStore::open(&path).unwrap();
+    let second = SqliteSessionStore::open(&path).unwrap();
+    first.put_challenge("auth", &challenge(SystemTime::now() + Duration::from_secs(60))).unwrap();
+
+    assert!(second.take_challenge("auth").unwrap().is_some());
+    assert!(first.take_challenge("auth").unwrap().is_none());
+}
+
+#[test]
+fn test_sqlite_session_store_adds_key_column_to_old_databases() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sessions.db");
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute_batch("CREATE TABLE sessions (token TEXT PRIMARY KEY NOT NULL, user TEXT NOT NULL, expires_at INTEGER NOT NULL);")
+        .unwrap();
+
+    let store = SqliteSessionStore::open(&path).unwrap();
+
+    let later = SystemTime::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+    let keyed = Session {
+        key: Some(SessionKey([7; 32])),
+        ..session(later)
+    };
+    store.put_session("keyed", &keyed).unwrap();
+    assert_eq!(store.get_session("keyed").unwrap(), Some(keyed));
+}
+
+#[test]
+fn test_database_holds_neither_session_tokens_nor_keys() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("sessions.db");
+    let registry = SessionRegistry::new(Arc::new(SqliteSessionStore::open(&path).unwrap()), Duration::from_secs(60));
+    let key = SessionKey([7; 32]);
+
+    let (token, _) = registry.issue("alice", Some(key.clone())).unwrap();
+
+    let (stored_token, stored_key): (String, String) = rusqlite::Connection::open(&path)
+        .unwrap()
+        .query_row("SELECT token, key FROM sessions", [], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap();
+    assert_ne!(stored_token, token);
+    assert_ne!(stored_key, hex::encode(key.0));
+    assert_eq!(registry.validate(&token).unwrap().unwrap().key, Some(key));
+}
+
+#[tokio::test]
+async fn test_two_servers_share_login_state() {
+    // Arrange: two services on the same user and session databases, as two processes would be
+    let dir = tempfile::tempdir().unwrap();
+    let config = SessionStoreConfig::Sqlite(dir.path().join("sessions.db"));
+    let users: Arc<dyn UserStore> = Arc::new(MemoryUserStore::new());
+    let group = test_params();
+    let first = AuthService::new(group.clone())
+        .with_user_store(users.clone())
+        .with_session_store(config.open().unwrap());
+    let second = AuthService::new(group.clone())
+        .with_user_store(users)
+        .with_session_store(config.open().unwrap());
+
+    let x = BigInt::from(6);
+    register(&first, &group, &x).await;
+
+    let k = BigInt::from(7);
+    let (r1, r2) = protocol::commitment(&group, &k);
+    let challenge = first
+        .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+            user: "test_user".to_string(),
+            r1: r1.to_string(),
+            r2: r2.to_string(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let s = protocol::response(&group, &k, &challenge.c.parse().unwrap(), &x);
+
+    // Act: answer on the second server, validate on the first
+    let login = second
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id.clone(),
+            s: s.to_string(),
+            client_share: String::new(),
+        }))
+        .await
+        .unwrap()
+        .into_inner();
+    let validated = first
+        .validate_session(Request::new(ValidateSessionRequest { session_id: login.session_id }))
+        .await
+        .unwrap()
+        .into_inner();
+    let replay = first
+        .verify_authentication(Request::new(AuthenticationAnswerRequest {
+            auth_id: challenge.auth_id,
+            s: s.to_string(),
+            client_share: String::new(),
+        }))
+        .await;
+
+    // Assert
+    assert_eq!(validated.user, "test_user");
+    assert_eq!(replay.unwrap_err().code(), tonic::Code::NotFound);
+}
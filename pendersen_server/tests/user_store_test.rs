@@ -0,0 +1,224 @@
+use pendersen_core::{protocol, Group, KdfParameters, RistrettoParameters};
+use pendersen_server::pb::auth_server::Auth;
+use pendersen_server::pb::KdfParametersRequest;
+use pendersen_server::{AuthService, JsonFileUserStore, MemoryUserStore, SqliteUserStore, StoredUser, UserStore, UserStoreConfig};
+
+use num_bigint::BigInt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tonic::Request;
+
+mod common;
+use common::{login_request, now, register_request};
+
+fn alice() -> StoredUser {
+    StoredUser {
+        y1: "4".to_string(),
+        y2: "9".to_string(),
+        kdf: Some(KdfParameters {
+            salt: b"0123456789abcdef".to_vec(),
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }),
+    }
+}
+
+fn bob() -> StoredUser {
+    StoredUser {
+        y1: "3".to_string(),
+        y2: "13".to_string(),
+        kdf: None,
+    }
+}
+
+// The behaviour every implementation must share
+fn check_contract(store: &dyn UserStore) {
+    assert_eq!(store.get("alice").unwrap(), None);
+    assert!(store.insert_if_absent("alice", &alice()).unwrap());
+    assert!(!store.insert_if_absent("alice", &bob()).unwrap());
+    assert_eq!(store.get("alice").unwrap(), Some(alice()));
+
+    assert!(!store.update("bob", &bob()).unwrap());
+    assert!(store.insert_if_absent("bob", &bob()).unwrap());
+    assert!(store.update("alice", &bob()).unwrap());
+    assert_eq!(store.get("alice").unwrap(), Some(bob()));
+    assert_eq!(store.list().unwrap(), vec!["alice".to_string(), "bob".to_string()]);
+
+    // replace only succeeds against the record currently stored
+    assert!(!store.replace("alice", &alice(), &alice()).unwrap());
+    assert!(store.replace("alice", &bob(), &alice()).unwrap());
+    assert_eq!(store.get("alice").unwrap(), Some(alice()));
+    assert!(store.replace("alice", &alice(), &bob()).unwrap());
+    assert!(!store.replace("carol", &bob(), &alice()).unwrap());
+
+    assert!(store.delete("alice").unwrap());
+    assert!(!store.delete("alice").unwrap());
+    assert_eq!(store.list().unwrap(), vec!["bob".to_string()]);
+}
+
+#[test]
+fn test_memory_store_contract() {
+    check_contract(&MemoryUserStore::new());
+}
+
+#[test]
+fn test_json_store_contract() {
+    let dir = tempfile::tempdir().unwrap();
+    check_contract(&JsonFileUserStore::open(dir.path().join("users.json")).unwrap());
+}
+
+#[test]
+fn test_sqlite_store_contract() {
+    check_contract(&SqliteUserStore::in_memory().unwrap());
+}
+
+#[test]
+fn test_json_store_survives_reopen_without_leftovers() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.json");
+    let store = JsonFileUserStore::open(&path).unwrap();
+    store.insert_if_absent("alice", &alice()).unwrap();
+    store.insert_if_absent("bob", &bob()).unwrap();
+    drop(store);
+
+    // Act
+    let reopened = JsonFileUserStore::open(&path).unwrap();
+
+    // Assert
+    assert_eq!(reopened.get("alice").unwrap(), Some(alice()));
+    assert_eq!(reopened.get("bob").unwrap(), Some(bob()));
+    let files: Vec<PathBuf> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().path()).collect();
+    assert_eq!(files, vec![path]);
+}
+
+#[test]
+fn test_json_store_rejects_corrupt_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.json");
+    std::fs::write(&path, "{\"users\": ").unwrap();
+
+    let err = JsonFileUserStore::open(&path).err().unwrap();
+
+    assert!(err.to_string().contains("corrupt"));
+}
+
+#[test]
+fn test_sqlite_store_survives_reopen() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.db");
+    let store = SqliteUserStore::open(&path).unwrap();
+    store.insert_if_absent("alice", &alice()).unwrap();
+    drop(store);
+
+    // Act
+    let reopened = SqliteUserStore::open(&path).unwrap();
+
+    // Assert
+    assert_eq!(reopened.get("alice").unwrap(), Some(alice()));
+}
+
+#[test]
+fn test_sqlite_replace_is_checked_across_connections() {
+    // Arrange: two connections to one file, as two server processes would have
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.db");
+    let first = SqliteUserStore::open(&path).unwrap();
+    let second = SqliteUserStore::open(&path).unwrap();
+    first.insert_if_absent("alice", &alice()).unwrap();
+    first.replace("alice", &alice(), &bob()).unwrap();
+
+    // Act: the second still holds the record it read before the change
+    let replaced = second.replace("alice", &alice(), &alice()).unwrap();
+
+    // Assert
+    assert!(!replaced);
+    assert_eq!(second.get("alice").unwrap(), Some(bob()));
+}
+
+#[test]
+fn test_sqlite_store_waits_for_another_writer() {
+    // Arrange: another process holds the write lock for a moment
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.db");
+    let store = SqliteUserStore::open(&path).unwrap();
+    let other = rusqlite::Connection::open(&path).unwrap();
+    other.execute_batch("BEGIN IMMEDIATE").unwrap();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        other.execute_batch("COMMIT").unwrap();
+    });
+
+    // Act
+    let inserted = store.insert_if_absent("alice", &alice());
+
+    // Assert
+    writer.join().unwrap();
+    assert!(inserted.unwrap());
+    let second = SqliteUserStore::open(&path).unwrap();
+    assert_eq!(second.get("alice").unwrap(), Some(alice()));
+    let journal_mode: String = rusqlite::Connection::open(&path)
+        .unwrap()
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(journal_mode, "wal");
+}
+
+#[test]
+fn test_user_store_config_parsing() {
+    assert_eq!(UserStoreConfig::parse("memory").unwrap(), UserStoreConfig::Memory);
+    assert_eq!(
+        UserStoreConfig::parse("json:/var/lib/pendersen/users.json").unwrap(),
+        UserStoreConfig::JsonFile(PathBuf::from("/var/lib/pendersen/users.json"))
+    );
+    assert_eq!(UserStoreConfig::parse("sqlite:users.db").unwrap(), UserStoreConfig::Sqlite(PathBuf::from("users.db")));
+    assert!(UserStoreConfig::parse("sqlite:").is_err());
+    assert!(UserStoreConfig::parse("redis:localhost").is_err());
+}
+
+#[tokio::test]
+async fn test_registration_survives_server_restart() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let config = UserStoreConfig::Sqlite(dir.path().join("users.db"));
+    let group = RistrettoParameters::default();
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let first = AuthService::new(group.clone()).with_user_store(config.open().unwrap());
+    first.register(Request::new(register_request(&group, &x, None))).await.unwrap();
+    drop(first);
+
+    // Act
+    let restarted = AuthService::new(group.clone()).with_user_store(config.open().unwrap());
+    let login = restarted.login(Request::new(login_request(&group, &x, &x, now()))).await;
+    let again = restarted.register(Request::new(register_request(&group, &x, None))).await;
+
+    // Assert
+    assert!(login.is_ok());
+    assert_eq!(again.unwrap_err().code(), tonic::Code::AlreadyExists);
+}
+
+#[tokio::test]
+async fn test_json_store_backs_auth_service() {
+    // Arrange
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.json");
+    let group = RistrettoParameters::default();
+    let x = group.scalar_from_bigint(&BigInt::from(42));
+    let store: Arc<dyn UserStore> = Arc::new(JsonFileUserStore::open(&path).unwrap());
+    let auth_service = AuthService::new(group.clone()).with_user_store(store);
+
+    // Act
+    auth_service.register(Request::new(register_request(&group, &x, None))).await.unwrap();
+
+    // Assert
+    let (y1, _) = protocol::public_key(&group, &x);
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(contents.contains(&group.encode_element(&y1)));
+    let status = auth_service
+        .get_kdf_parameters(Request::new(KdfParametersRequest { user: "nobody".to_string() }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+}